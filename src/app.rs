@@ -1,29 +1,54 @@
 use egui::RichText;
+use std::collections::BTreeMap;
 use std::future::Future;
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 use strum::IntoEnumIterator;
 
+use crate::bibliography::{merge_references, parse_bibliography};
+use crate::broadening::{doppler_fwhm, isotope_mass, mass_number, nuclear_spin};
+use crate::diagram::{layout_scheme, level_energies, lower_energy, paint, LabelKind, Target};
+use crate::diagram_export::{render_png, render_svg, EXPORT_HEIGHT, EXPORT_WIDTH};
+use crate::documents::SchemeDocument;
+use crate::doi_resolver::{CacheResolver, DoiMetadata, DoiResolver, ResolverChain};
+use crate::history::History;
+use crate::levels::{LevelMismatch, LevelTable, Suggestion, DEFAULT_LEVEL_TOLERANCE};
+use crate::migrations::{migrate_persisted, PersistedLayout, FORMAT_VERSION};
+use crate::population::thermal_populations;
+use crate::saturation::{
+    fit_saturation, layout_comparison, DerivedQuantities, LaserParameters, CURVE_COLORS,
+};
+use crate::strength::{step_input, StrengthInput, StrengthQuantity};
 use crate::{
-    create_email_link, create_gh_issue, create_json_output, is_doi, load_config_file, Elements,
-    GroundState, Lasers, ReferenceEntry, SaturationCurve, SaturationCurveUnit, Transition,
+    create_drawer_output, create_email_link, create_gh_issue, create_json_output,
+    create_references_markdown, insert_transition, load_config_file, Elements, Lasers,
+    ReferenceEntry, ReferenceId, ReferenceKind, SaturationCurve, SaturationCurveUnit, StepSource,
     TransitionUnit,
 };
 
+/// Maximum number of steps of a scheme.
+pub const STEPS: usize = 7;
+
 /// We derive Deserialize/Serialize to persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
 pub struct TemplateApp {
-    pub notes: String,
-    pub references: Vec<ReferenceEntry>,
-    pub saturation_curves: Vec<SaturationCurve>,
-    pub scheme_element: Elements,
-    pub scheme_gs: GroundState,
-    pub scheme_ip_term_symbol: String,
-    pub scheme_lasers: Lasers,
-    pub scheme_transitions: [Transition; 7],
-    pub scheme_last_step_to_ip: bool,
-    pub scheme_unit: TransitionUnit,
+    /// Version of the persisted state, missing (i.e., 0) if saved before versioning.
+    #[serde(default)]
+    pub format_version: u64,
+    /// Form data of the active tab.
+    pub document: SchemeDocument,
     pub submitted_by: String,
+    /// Show the diagram of the scheme next to the form.
+    pub show_diagram: bool,
+    /// Tables of known levels by element, loaded by the user.
+    pub level_tables: BTreeMap<String, LevelTable>,
+    /// Tolerance in cm¯¹ when checking the levels against the level table.
+    pub level_tolerance: f64,
+    /// Documents of all tabs, the entry of the active tab is an empty placeholder.
+    pub documents: Vec<SchemeDocument>,
+    /// Index of the tab that is shown.
+    pub active_document: usize,
     #[serde(skip)]
     pub rimsschemedrawer_in: String,
     #[serde(skip)]
@@ -43,48 +68,99 @@ pub struct TemplateApp {
     #[serde(skip)]
     sat_tmp_ydat_unc: String,
     #[serde(skip)]
+    sat_tmp_repetition_rate: String,
+    #[serde(skip)]
+    sat_tmp_wavelength: String,
+    #[serde(skip)]
+    sat_tmp_beam_diameter: String,
+    #[serde(skip)]
+    sat_detail: Option<usize>,
+    /// Titles of the saturation curves that are compared.
+    #[serde(skip)]
+    sat_compare: Vec<String>,
+    #[serde(skip)]
     text_channel: (Sender<String>, Receiver<String>),
     #[serde(skip)]
+    bibliography_channel: (Sender<String>, Receiver<String>),
+    #[serde(skip)]
+    level_table_channel: LevelTableChannel,
+    #[serde(skip)]
+    error_level_table: String,
+    #[serde(skip)]
+    doi_cache: Option<Arc<CacheResolver>>,
+    #[serde(skip)]
+    doi_cache_channel: (Sender<String>, Receiver<String>),
+    #[serde(skip)]
+    doi_channel: DoiChannel,
+    #[serde(skip)]
+    doi_pending: usize,
+    #[serde(skip)]
+    doi_resolved: usize,
+    #[serde(skip)]
+    info_doi_resolution: String,
+    #[serde(skip)]
     reference_id: String,
     #[serde(skip)]
     reference_author: String,
     #[serde(skip)]
     reference_year: String,
     #[serde(skip)]
+    reference_title: String,
+    #[serde(skip)]
+    reference_journal: String,
+    #[serde(skip)]
+    reference_volume: String,
+    #[serde(skip)]
+    reference_pages: String,
+    #[serde(skip)]
+    reference_kind: ReferenceKind,
+    #[serde(skip)]
     error_reference: String,
     #[serde(skip)]
+    info_reference_import: String,
+    #[serde(skip)]
     error_rimsschemedrawer_in: String,
     #[serde(skip)]
+    info_rimsschemedrawer_in: String,
+    #[serde(skip)]
     error_saturation: String,
     #[serde(skip)]
     error_submission: String,
+    #[serde(skip)]
+    error_diagram: String,
+    #[serde(skip)]
+    selected_step: Option<usize>,
+    #[serde(skip)]
+    diagram_drag: Option<(LabelKind, usize)>,
+    #[serde(skip)]
+    diagram_context_step: Option<usize>,
+    #[serde(skip)]
+    strength_converter: Option<StrengthConverter>,
+    #[serde(skip)]
+    expanded_steps: [bool; STEPS],
+    #[serde(skip)]
+    history: History,
+    /// Widget with the focus when the history was last checked for edits.
+    #[serde(skip)]
+    history_focus: Option<egui::Id>,
+    /// Form data was changed without input, e.g., by a loaded file, and is not recorded yet.
+    #[serde(skip)]
+    history_changed: bool,
+    #[serde(skip)]
+    confirm_clear: bool,
 }
 
 impl Default for TemplateApp {
     fn default() -> Self {
         Self {
-            notes: String::new(),
-            references: Vec::new(),
-            saturation_curves: Vec::new(),
-            scheme_element: Elements::H,
-            scheme_gs: GroundState {
-                level: "0".to_owned(),
-                term_symbol: String::new(),
-            },
-            scheme_ip_term_symbol: String::new(),
-            scheme_lasers: Lasers::TiSa,
-            scheme_transitions: [
-                Transition::new_empty(),
-                Transition::new_empty(),
-                Transition::new_empty(),
-                Transition::new_empty(),
-                Transition::new_empty(),
-                Transition::new_empty(),
-                Transition::new_empty(),
-            ],
-            scheme_last_step_to_ip: false,
-            scheme_unit: TransitionUnit::CM1,
+            format_version: FORMAT_VERSION,
+            document: SchemeDocument::default(),
             submitted_by: String::new(),
+            show_diagram: true,
+            level_tables: BTreeMap::new(),
+            level_tolerance: DEFAULT_LEVEL_TOLERANCE,
+            documents: Vec::new(),
+            active_document: 0,
             sat_tmp_title: String::new(),
             sat_tmp_notes: String::new(),
             sat_tmp_unit: SaturationCurveUnit::WCM2,
@@ -93,19 +169,125 @@ impl Default for TemplateApp {
             sat_tmp_xdat_unc: String::new(),
             sat_tmp_ydat: String::new(),
             sat_tmp_ydat_unc: String::new(),
+            sat_tmp_repetition_rate: String::new(),
+            sat_tmp_wavelength: String::new(),
+            sat_tmp_beam_diameter: String::new(),
+            sat_detail: None,
+            sat_compare: Vec::new(),
             rimsschemedrawer_in: String::new(),
             text_channel: channel(),
+            bibliography_channel: channel(),
+            level_table_channel: channel(),
+            error_level_table: String::new(),
+            doi_cache: None,
+            doi_cache_channel: channel(),
+            doi_channel: channel(),
+            doi_pending: 0,
+            doi_resolved: 0,
+            info_doi_resolution: String::new(),
             reference_id: String::new(),
             reference_author: String::new(),
             reference_year: String::new(),
+            reference_title: String::new(),
+            reference_journal: String::new(),
+            reference_volume: String::new(),
+            reference_pages: String::new(),
+            reference_kind: ReferenceKind::Article,
             error_reference: String::new(),
+            info_reference_import: String::new(),
             error_rimsschemedrawer_in: String::new(),
+            info_rimsschemedrawer_in: String::new(),
             error_saturation: String::new(),
             error_submission: String::new(),
+            error_diagram: String::new(),
+            selected_step: None,
+            diagram_drag: None,
+            diagram_context_step: None,
+            strength_converter: None,
+            expanded_steps: [false; STEPS],
+            history: History::default(),
+            history_focus: None,
+            history_changed: false,
+            confirm_clear: false,
+        }
+    }
+}
+
+/// Entries of the transition strength converter of a step.
+struct StrengthConverter {
+    step: usize,
+    quantity: StrengthQuantity,
+    value: String,
+    wavelength: String,
+    j_lower: String,
+    j_upper: String,
+    branching_ratio: String,
+    error: String,
+}
+
+impl StrengthConverter {
+    /// Converter pre-filled with the given input, including its value if `with_value` is set.
+    fn new(step: usize, input: StrengthInput, with_value: bool) -> Self {
+        let text = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+        Self {
+            step,
+            quantity: input.quantity,
+            value: match with_value {
+                true => input.value.to_string(),
+                false => String::new(),
+            },
+            wavelength: input
+                .wavelength
+                .map(|w| format!("{:.4}", w))
+                .unwrap_or_default(),
+            j_lower: text(input.j_lower),
+            j_upper: text(input.j_upper),
+            branching_ratio: text(input.branching_ratio),
+            error: String::new(),
         }
     }
+
+    fn input(&self) -> Result<StrengthInput, String> {
+        let optional = |name: &str, v: &str| match v.trim() {
+            "" => Ok(None),
+            v => v
+                .parse::<f64>()
+                .map(Some)
+                .map_err(|_| format!("{} is not a number.", name)),
+        };
+        let is_lifetime = self.quantity == StrengthQuantity::Lifetime;
+        Ok(StrengthInput {
+            quantity: self.quantity,
+            value: optional("Value", &self.value)?.ok_or("Please enter a value.".to_string())?,
+            wavelength: match is_lifetime {
+                true => None,
+                false => optional("Wavelength", &self.wavelength)?,
+            },
+            j_lower: match is_lifetime {
+                true => None,
+                false => optional("J of the lower level", &self.j_lower)?,
+            },
+            j_upper: match is_lifetime {
+                true => None,
+                false => optional("J of the upper level", &self.j_upper)?,
+            },
+            branching_ratio: match is_lifetime {
+                true => optional("Branching ratio", &self.branching_ratio)?,
+                false => None,
+            },
+        })
+    }
 }
 
+/// Channel to receive resolved DOI metadata from the background thread.
+type DoiChannel = (
+    Sender<(String, Result<Option<DoiMetadata>, String>)>,
+    Receiver<(String, Result<Option<DoiMetadata>, String>)>,
+);
+
+/// Channel to receive a level table file together with the element it was selected for.
+type LevelTableChannel = (Sender<(String, String)>, Receiver<(String, String)>);
+
 impl TemplateApp {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
@@ -115,22 +297,592 @@ impl TemplateApp {
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
         if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+            let mut app: Self = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+            let flat_document = eframe::get_value::<PersistedLayout>(storage, eframe::APP_KEY)
+                .filter(|layout| !layout.has_document)
+                .and_then(|_| eframe::get_value(storage, eframe::APP_KEY));
+            migrate_persisted(&mut app, flat_document);
+            return app;
         }
 
         Default::default()
     }
+
+    /// Create the resolver for DOI metadata from the loaded cache file and, if enabled, Crossref.
+    fn doi_resolver(&self) -> ResolverChain {
+        let mut resolvers: Vec<Arc<dyn DoiResolver>> = Vec::new();
+        if let Some(cache) = &self.doi_cache {
+            resolvers.push(cache.clone());
+        }
+        #[cfg(all(feature = "crossref", not(target_arch = "wasm32")))]
+        resolvers.push(Arc::new(crate::CrossrefResolver));
+        ResolverChain(resolvers)
+    }
+
+    /// Reset the state of the view that belongs to a document, e.g., after switching tabs.
+    fn reset_document_view(&mut self) {
+        self.selected_step = None;
+        self.diagram_drag = None;
+        self.diagram_context_step = None;
+        self.strength_converter = None;
+        self.expanded_steps = [false; STEPS];
+        self.sat_detail = None;
+        self.sat_compare.clear();
+        self.error_diagram.clear();
+        self.error_submission.clear();
+        self.error_saturation.clear();
+    }
+
+    /// Swap two saturation curves, the fit details stay with the shown curve.
+    fn swap_saturation_curves(&mut self, a: usize, b: usize) {
+        self.document.saturation_curves.swap(a, b);
+        self.sat_detail = self.sat_detail.map(|detail| match detail {
+            _ if detail == a => b,
+            _ if detail == b => a,
+            _ => detail,
+        });
+    }
+
+    /// Undo the last edit, including an edit of the focused field that is not recorded yet.
+    fn undo(&mut self) {
+        let snapshot = self.snapshot();
+        if let Some(state) = self.history.undo(snapshot) {
+            // snapshots are always written by this app
+            _ = self.restore(state);
+        }
+    }
+
+    fn redo(&mut self) {
+        let snapshot = self.snapshot();
+        if let Some(state) = self.history.redo(snapshot) {
+            _ = self.restore(state);
+        }
+    }
+
+    /// Record the edits of this frame for undo. Widgets are only changed by pointer releases and
+    /// key or text input, other frames, e.g., of pointer movement, are skipped. Edits of a text
+    /// field are recorded once it loses the focus, drags once the pointer is released.
+    fn record_history(&mut self, ctx: &egui::Context) {
+        let focus = ctx.memory(|m| m.focused());
+        let focus_changed = focus != self.history_focus;
+        self.history_focus = focus;
+        let input = ctx.input(|i| {
+            i.pointer.any_released()
+                || i.events.iter().any(|e| {
+                    matches!(
+                        e,
+                        egui::Event::Key { .. }
+                            | egui::Event::Text(_)
+                            | egui::Event::Paste(_)
+                            | egui::Event::Cut
+                    )
+                })
+        });
+        if !focus_changed && !input && !self.history_changed {
+            return;
+        }
+        let editing = focus.is_some() && !focus_changed;
+        if !editing && !ctx.input(|i| i.pointer.any_down()) {
+            let snapshot = self.snapshot();
+            self.history.record(snapshot);
+            self.history_changed = false;
+        }
+    }
+
+    /// Insert an empty step at `index` of the scheme, e.g., from the context menu of the diagram.
+    fn insert_step(&mut self, index: usize) {
+        match insert_transition(&mut self.document.scheme_transitions, index) {
+            Ok(()) => {
+                self.document.diagram_offsets.insert(index);
+                self.expanded_steps[index..].rotate_right(1);
+                self.expanded_steps[index] = false;
+                self.selected_step = Some(index);
+                self.error_diagram.clear();
+            }
+            Err(e) => self.error_diagram = e,
+        }
+    }
+
+    /// Resolve the metadata of all DOI references without author or year in the background.
+    /// Results are received via the DOI channel, such that the UI thread is never blocked.
+    fn resolve_dois(&mut self, ctx: &egui::Context) {
+        self.info_doi_resolution.clear();
+        let dois: Vec<String> = self
+            .document
+            .references
+            .iter()
+            .filter(|r| r.needs_doi_metadata())
+            .map(|r| r.id.clone())
+            .collect();
+        if dois.is_empty() {
+            self.info_doi_resolution = "All DOI references are already resolved.".into();
+            return;
+        }
+
+        let resolver = self.doi_resolver();
+        if resolver.0.is_empty() {
+            self.error_reference = "Please load a DOI cache file first.".into();
+            return;
+        }
+
+        self.doi_pending += dois.len();
+        let sender = self.doi_channel.0.clone();
+        let ctx = ctx.clone();
+        execute(async move {
+            for doi in dois {
+                let result = resolver.resolve(&doi);
+                let _ = sender.send((doi, result));
+                ctx.request_repaint();
+            }
+        });
+    }
+}
+
+/// Label of a row in the scheme grid, highlighted if the step is selected in the diagram and
+/// flagged if it does not agree with the level table. `info` is shown as tooltip.
+fn row_label(
+    ui: &mut egui::Ui,
+    text: RichText,
+    selected: bool,
+    mismatch: Option<&LevelMismatch>,
+    info: Option<String>,
+) {
+    let text = match selected {
+        true => text.strong().color(ui.visuals().selection.stroke.color),
+        false => text,
+    };
+    let text = match mismatch {
+        Some(_) => text.color(ui.visuals().warn_fg_color),
+        None => text,
+    };
+    let hover: Vec<String> = mismatch
+        .map(|m| m.message.clone())
+        .into_iter()
+        .chain(info)
+        .collect();
+    let response = ui.label(text);
+    if !hover.is_empty() {
+        response.on_hover_text(hover.join("\n\n"));
+    }
+}
+
+/// Fit results of a saturation curve and the quantities derived from them.
+fn saturation_details(ui: &mut egui::Ui, curve: &SaturationCurve) {
+    ui.label(RichText::new(format!("Fit of {}", curve.title)).strong());
+    let fit = match fit_saturation(curve) {
+        Ok(fit) => fit,
+        Err(e) => {
+            ui.label(RichText::new(e).color(egui::Color32::RED).strong());
+            return;
+        }
+    };
+    let derived = DerivedQuantities::new(fit.saturation, &curve.units, &curve.laser);
+    let missing = "Requires more laser parameters.".to_string();
+    egui::Grid::new("saturation_detail_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            let saturation_name = match curve.units {
+                SaturationCurveUnit::WCM2 => "Saturation irradiance",
+                SaturationCurveUnit::W => "Saturation power",
+            };
+            ui.label(format!("{}:", saturation_name));
+            ui.label(format!(
+                "({:.3e} ± {:.1e}) {}",
+                fit.saturation, fit.saturation_unc, curve.units
+            ));
+            ui.end_row();
+            ui.label("Amplitude:");
+            ui.label(format!("{:.3e} ± {:.1e}", fit.amplitude, fit.amplitude_unc));
+            ui.end_row();
+            ui.label("Saturation fluence:");
+            ui.label(
+                derived
+                    .saturation_fluence
+                    .map_or(missing.clone(), |f| format!("{:.3e} J/cm²", f)),
+            )
+            .on_hover_text("Saturation irradiance divided by the repetition rate.");
+            ui.end_row();
+            ui.label("Cross-section:");
+            ui.label(
+                derived
+                    .cross_section
+                    .map_or(missing.clone(), |s| format!("{:.3e} cm²", s)),
+            )
+            .on_hover_text("Inverse of the photon fluence at saturation.");
+            ui.end_row();
+            ui.label("Power for 90% saturation:");
+            ui.label(
+                derived
+                    .power_90
+                    .map_or(missing.clone(), |p| format!("{:.3e} W", p)),
+            )
+            .on_hover_text("Nine times the saturation, converted with the beam area.");
+            ui.end_row();
+        });
+    if !curve.fit {
+        ui.label("The fit is not included in the submission, since fitting is switched off for this curve.");
+    }
+}
+
+/// Overlay of the compared saturation curves and their fit parameters side by side.
+fn saturation_comparison(ui: &mut egui::Ui, curves: &[&SaturationCurve]) {
+    ui.label(RichText::new("Comparison").strong());
+    if curves.iter().any(|c| c.units != curves[0].units) {
+        ui.label(
+            RichText::new(
+                "The curves have different units, their saturations cannot be compared directly.",
+            )
+            .color(ui.visuals().warn_fg_color),
+        );
+    }
+    let size = egui::vec2(
+        ui.available_width().min(COMPARISON_MAX_WIDTH),
+        COMPARISON_HEIGHT,
+    );
+    let layout = layout_comparison(curves, size.x, size.y);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    paint(&layout, &painter, response.rect);
+    ui.add_space(VERTICAL_SPACE);
+
+    let missing = "-".to_string();
+    egui::Grid::new("saturation_comparison_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            for header in [
+                "Curve",
+                "Saturation",
+                "Amplitude",
+                "Saturation fluence (J/cm²)",
+                "Cross-section (cm²)",
+                "Power for 90% (W)",
+            ] {
+                ui.label(RichText::new(header).strong());
+            }
+            ui.end_row();
+            for (it, curve) in curves.iter().enumerate() {
+                let [r, g, b] = CURVE_COLORS[it % CURVE_COLORS.len()];
+                ui.label(
+                    RichText::new(format!("✖ {}", curve.title))
+                        .color(egui::Color32::from_rgb(r, g, b)),
+                );
+                match fit_saturation(curve) {
+                    Ok(fit) => {
+                        let derived =
+                            DerivedQuantities::new(fit.saturation, &curve.units, &curve.laser);
+                        ui.label(format!(
+                            "({:.3e} ± {:.1e}) {}",
+                            fit.saturation, fit.saturation_unc, curve.units
+                        ));
+                        ui.label(format!("{:.3e} ± {:.1e}", fit.amplitude, fit.amplitude_unc));
+                        for value in [
+                            derived.saturation_fluence,
+                            derived.cross_section,
+                            derived.power_90,
+                        ] {
+                            ui.label(value.map_or(missing.clone(), |v| format!("{:.3e}", v)));
+                        }
+                    }
+                    Err(e) => {
+                        ui.label(RichText::new(e).color(egui::Color32::RED));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// Cell of the scheme grid with a value and its uncertainty, to be placed in a horizontal layout.
+/// A value entered in concise notation, e.g., "25123.45(3)", is split into both fields.
+/// `reserve` is the width kept free for further widgets in the cell. Returns the response of the
+/// value field.
+fn value_with_uncertainty(
+    ui: &mut egui::Ui,
+    value: &mut String,
+    unc: &mut String,
+    reserve: f32,
+) -> egui::Response {
+    let value_width = ui.available_width() - UNCERTAINTY_INPUT_WIDTH - PLUS_MINUS_WIDTH - reserve;
+    let response = ui.add(
+        egui::TextEdit::singleline(value)
+            .desired_width(value_width.max(UNCERTAINTY_INPUT_WIDTH))
+            .horizontal_align(egui::Align::RIGHT),
+    );
+    if response.changed() {
+        if let Some((v, u)) = crate::split_concise(value) {
+            *value = v;
+            *unc = u;
+        }
+    }
+    ui.label("±");
+    ui.add(
+        egui::TextEdit::singleline(unc)
+            .desired_width(UNCERTAINTY_INPUT_WIDTH)
+            .horizontal_align(egui::Align::RIGHT),
+    )
+    .on_hover_text(
+        "Optional uncertainty. Values can also be entered in concise notation, e.g., 25123.45(3).",
+    );
+    response
+}
+
+/// Show suggestions of known levels below a cell while it is edited and return the picked one.
+/// The suggestions are only computed when the cell changes or the popup is open.
+fn suggestion_popup(
+    ui: &mut egui::Ui,
+    response: &egui::Response,
+    id_source: impl std::hash::Hash,
+    suggestions: impl FnOnce() -> Vec<Suggestion>,
+) -> Option<Suggestion> {
+    let id = ui.make_persistent_id(id_source);
+    let is_open = ui.memory(|mem| mem.is_popup_open(id));
+    if !(is_open || response.gained_focus() || response.changed()) {
+        return None;
+    }
+
+    let suggestions = suggestions();
+    if suggestions.is_empty() {
+        if is_open {
+            ui.memory_mut(|mem| mem.close_popup());
+        }
+        return None;
+    }
+    if !is_open {
+        ui.memory_mut(|mem| mem.open_popup(id));
+    }
+
+    let mut picked = None;
+    egui::popup::popup_below_widget(ui, id, response, |ui| {
+        ui.set_min_width(SUGGESTION_MIN_WIDTH);
+        for suggestion in suggestions {
+            if ui.selectable_label(false, &suggestion.label).clicked() {
+                picked = Some(suggestion);
+            }
+        }
+    });
+    if picked.is_some() {
+        ui.memory_mut(|mem| mem.close_popup());
+    }
+    picked
 }
 
 impl eframe::App for TemplateApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Undo and redo before the widgets, such that text fields do not handle the shortcuts
+        // themselves. Redo first, since the shortcut of undo also matches with shift.
+        if ctx.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT)) {
+            self.redo();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+            self.undo();
+        }
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 egui::widgets::global_dark_light_mode_buttons(ui);
+                ui.separator();
+                ui.checkbox(&mut self.show_diagram, "Show diagram");
+                ui.separator();
+                if ui
+                    .add_enabled(self.history.can_undo(), egui::Button::new("Undo"))
+                    .on_hover_text(ctx.format_shortcut(&UNDO_SHORTCUT))
+                    .clicked()
+                {
+                    self.undo();
+                }
+                if ui
+                    .add_enabled(self.history.can_redo(), egui::Button::new("Redo"))
+                    .on_hover_text(ctx.format_shortcut(&REDO_SHORTCUT))
+                    .clicked()
+                {
+                    self.redo();
+                }
+            });
+
+            // Tabs of the open schemes
+            ui.horizontal_wrapped(|ui| {
+                let count = self.document_count();
+                for (it, label) in self.document_labels().into_iter().enumerate() {
+                    if ui
+                        .selectable_label(it == self.active_document, label)
+                        .clicked()
+                    {
+                        self.select_document(it);
+                        self.reset_document_view();
+                    }
+                }
+                if ui
+                    .button("New scheme")
+                    .on_hover_text("Open an empty scheme in a new tab.")
+                    .clicked()
+                {
+                    self.add_document();
+                    self.reset_document_view();
+                }
+                if count > 1
+                    && ui
+                        .button("Close scheme")
+                        .on_hover_text("Close the scheme of the current tab.")
+                        .clicked()
+                {
+                    self.close_document(self.active_document);
+                    self.reset_document_view();
+                }
             });
         });
 
+        if self.show_diagram {
+            egui::SidePanel::right("diagram_panel")
+                .resizable(true)
+                .default_width(DIAGRAM_DEFAULT_WIDTH)
+                .show(ctx, |ui| {
+                    ui.heading(RichText::new("Scheme diagram").strong());
+                    ui.add_space(VERTICAL_SPACE);
+                    ui.horizontal(|ui| {
+                        let svg = ui.button("Download SVG").clicked();
+                        let png = ui.button("Download PNG").clicked();
+                        if svg || png {
+                            self.error_diagram.clear();
+                            let contents = layout_scheme(self, EXPORT_WIDTH, EXPORT_HEIGHT)
+                                .and_then(|layout| match svg {
+                                    true => Ok(render_svg(&layout).into_bytes()),
+                                    false => render_png(&layout, PNG_EXPORT_SCALE),
+                                });
+                            match contents {
+                                Ok(contents) => {
+                                    let ext = if svg { "svg" } else { "png" };
+                                    let filter = [ext];
+                                    let task = rfd::AsyncFileDialog::new()
+                                        .set_file_name(format!(
+                                            "{:?}_scheme.{}",
+                                            self.document.scheme_element, ext
+                                        ))
+                                        .add_filter(
+                                            format!("{} image", ext.to_uppercase()),
+                                            &filter,
+                                        )
+                                        .save_file();
+                                    execute(async move {
+                                        let file = task.await;
+                                        if let Some(file) = file {
+                                            _ = file.write(&contents).await
+                                        }
+                                    });
+                                }
+                                Err(e) => self.error_diagram = e,
+                            }
+                        }
+                    });
+                    if !self.error_diagram.is_empty() {
+                        ui.label(
+                            RichText::new(&self.error_diagram)
+                                .color(egui::Color32::RED)
+                                .strong(),
+                        );
+                    }
+                    ui.add_space(VERTICAL_SPACE);
+                    let size = ui.available_size();
+                    match layout_scheme(self, size.x, size.y) {
+                        Ok(layout) => {
+                            let (response, painter) =
+                                ui.allocate_painter(size, egui::Sense::click_and_drag());
+                            let rect = response.rect;
+                            let target_at = |pos: Option<egui::Pos2>| {
+                                pos.and_then(|p| {
+                                    layout.target_at([p.x - rect.min.x, p.y - rect.min.y])
+                                })
+                            };
+
+                            // Drag labels to move them, click levels to select the step
+                            if response.drag_started() {
+                                self.diagram_drag =
+                                    match target_at(ui.input(|i| i.pointer.press_origin())) {
+                                        Some(Target::Label(kind, index)) => Some((kind, index)),
+                                        _ => None,
+                                    };
+                            }
+                            if let (true, Some((kind, index))) =
+                                (response.dragged(), self.diagram_drag)
+                            {
+                                let delta = response.drag_delta();
+                                let offset = self.document.diagram_offsets.get_mut(kind, index);
+                                offset[0] += delta.x;
+                                offset[1] += delta.y;
+                            }
+                            if response.drag_stopped() {
+                                self.diagram_drag = None;
+                            }
+                            if response.clicked() {
+                                self.selected_step =
+                                    match target_at(response.interact_pointer_pos()) {
+                                        Some(Target::Step(index) | Target::Label(_, index)) => {
+                                            Some(index)
+                                        }
+                                        None => None,
+                                    };
+                            }
+                            if response.secondary_clicked() {
+                                self.diagram_context_step =
+                                    match target_at(response.interact_pointer_pos()) {
+                                        Some(Target::Step(index) | Target::Label(_, index)) => {
+                                            Some(index)
+                                        }
+                                        None => None,
+                                    };
+                            }
+                            match target_at(response.hover_pos()) {
+                                Some(Target::Label(..)) => {
+                                    ui.ctx().set_cursor_icon(egui::CursorIcon::Grab)
+                                }
+                                Some(Target::Step(_)) => {
+                                    ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand)
+                                }
+                                None => (),
+                            }
+
+                            paint(&layout, &painter, rect);
+                            for hit in layout
+                                .targets
+                                .iter()
+                                .filter(|h| Some(h.target) == self.selected_step.map(Target::Step))
+                            {
+                                painter.rect_stroke(
+                                    egui::Rect::from_min_max(
+                                        rect.min + egui::vec2(hit.min[0], hit.min[1]),
+                                        rect.min + egui::vec2(hit.max[0], hit.max[1]),
+                                    ),
+                                    2.0,
+                                    ui.visuals().selection.stroke,
+                                );
+                            }
+
+                            if let Some(index) = self.diagram_context_step {
+                                response.context_menu(|ui| {
+                                    ui.label(RichText::new(format!("Step {}", index + 1)).strong());
+                                    let trans = &mut self.document.scheme_transitions[index];
+                                    let low_lying = ui.checkbox(&mut trans.low_lying, "Low-lying");
+                                    let forbidden = ui.checkbox(&mut trans.forbidden, "Forbidden");
+                                    if (low_lying | forbidden).changed() {
+                                        ui.close_menu();
+                                    }
+                                    ui.separator();
+                                    if ui.button("Insert step above").clicked() {
+                                        self.insert_step(index + 1);
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("Insert step below").clicked() {
+                                        self.insert_step(index);
+                                        ui.close_menu();
+                                    }
+                                });
+                            }
+                        }
+                        Err(e) => {
+                            ui.label(RichText::new(e).color(egui::Color32::RED).strong());
+                        }
+                    }
+                });
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 // ui.heading("Resonance Ionization Scheme Submission"
@@ -152,7 +904,7 @@ impl eframe::App for TemplateApp {
                 ui.heading(RichText::new("Notes").strong());
                 ui.add_space(VERTICAL_SPACE);
 
-                ui.text_edit_multiline(&mut self.notes).on_hover_text(
+                ui.text_edit_multiline(&mut self.document.notes).on_hover_text(
                     "Add any notes for your scheme here. You can use Markdown commands for formatting.",
                 );
                 ui.add_space(VERTICAL_SPACE);
@@ -203,11 +955,16 @@ impl eframe::App for TemplateApp {
                 // deal with uploaded file
                 if let Ok(text) = self.text_channel.1.try_recv() {
                     self.error_rimsschemedrawer_in.clear();
+                    self.info_rimsschemedrawer_in.clear();
                     self.rimsschemedrawer_in = text;
                     match load_config_file(self) {
-                        Ok(_) => (),
+                        Ok(report) => self.info_rimsschemedrawer_in = report.summary(),
                         Err(e) => self.error_rimsschemedrawer_in = e,
                     };
+                    self.history_changed = true;
+                }
+                if !self.info_rimsschemedrawer_in.is_empty() {
+                    ui.label(&self.info_rimsschemedrawer_in);
                 }
 
                 ui.add_space(VERTICAL_SPACE);
@@ -216,32 +973,169 @@ impl eframe::App for TemplateApp {
                 ui.horizontal(|ui| {
                     ui.label("Element:");
                     egui::ComboBox::from_id_source("Element")
-                        .selected_text(format!("{:?}", self.scheme_element))
+                        .selected_text(format!("{:?}", self.document.scheme_element))
                         .show_ui(ui, |ui| {
                             for element in Elements::iter() {
                                 let tmp_label = format!("{:?}", &element).to_owned();
-                                ui.selectable_value(&mut self.scheme_element, element, tmp_label);
+                                ui.selectable_value(&mut self.document.scheme_element, element, tmp_label);
                             }
                         });
                 });
                 ui.add_space(VERTICAL_SPACE);
 
+                // Table of known levels for autocompletion
+                let element_key = format!("{:?}", self.document.scheme_element);
+                ui.horizontal(|ui| {
+                    if ui.button("Load level table")
+                        .on_hover_text("Select a level table of this element in CSV format, as exported from the NIST Atomic Spectra Database. Known levels are then suggested while typing levels and term symbols.")
+                        .clicked() {
+                        let sender = self.level_table_channel.0.clone();
+                        let element = element_key.clone();
+                        let filter = ["csv"];
+                        let task = rfd::AsyncFileDialog::new()
+                            .add_filter("NIST ASD level table", &filter)
+                            .pick_file();
+                        let ctx = ui.ctx().clone();
+                        execute(async move {
+                            let file = task.await;
+                            if let Some(file) = file {
+                                let text = file.read().await;
+                                let _ = sender.send((element, String::from_utf8_lossy(&text).to_string()));
+                                ctx.request_repaint();
+                            }
+                        });
+                    }
+                    if let Some(table) = self.level_tables.get(&element_key) {
+                        ui.label(format!("{} known levels of {}.", table.levels.len(), element_key));
+                        if ui.button("Remove").clicked() {
+                            self.level_tables.remove(&element_key);
+                        }
+                    }
+                    if !self.error_level_table.is_empty() {
+                        ui.label(
+                            RichText::new(&self.error_level_table)
+                                .color(egui::Color32::RED)
+                                .strong(),
+                        );
+                    }
+                });
+                if let Ok((element, text)) = self.level_table_channel.1.try_recv() {
+                    self.error_level_table.clear();
+                    match LevelTable::from_nist_csv(&text) {
+                        Ok(table) => {
+                            self.level_tables.insert(element, table);
+                        }
+                        Err(e) => self.error_level_table = e,
+                    }
+                }
+                ui.add_space(VERTICAL_SPACE);
+
                 // Units
                 ui.horizontal(|ui| {
                     ui.label("Unit:");
                     ui.radio_value(
-                        &mut self.scheme_unit,
+                        &mut self.document.scheme_unit,
                         TransitionUnit::CM1,
                         TransitionUnit::CM1.to_string(),
                     );
                     ui.radio_value(
-                        &mut self.scheme_unit,
+                        &mut self.document.scheme_unit,
                         TransitionUnit::NM,
                         TransitionUnit::NM.to_string(),
                     );
                 });
                 ui.add_space(VERTICAL_SPACE);
 
+                // Temperature
+                ui.horizontal(|ui| {
+                    ui.label("Atomizer temperature (K):");
+                    ui.add(
+                        egui::DragValue::new(&mut self.document.temperature)
+                            .speed(10.0)
+                            .clamp_range(1.0..=10000.0),
+                    )
+                    .on_hover_text("Used to estimate the thermal populations of the ground state and the low-lying states, and the Doppler widths of the transitions.");
+                    ui.label("Isotope (mass number):");
+                    ui.add(egui::TextEdit::singleline(&mut self.document.isotope).desired_width(UNCERTAINTY_INPUT_WIDTH))
+                        .on_hover_text("Leave empty for the natural element, whose standard atomic weight is then used.");
+                    if let Ok(Some(_)) = mass_number(&self.document.scheme_element, &self.document.isotope) {
+                        match nuclear_spin(&self.document.scheme_element, &self.document.isotope, "") {
+                            Some(spin) => ui.label(format!("Nuclear spin: {} (even-even nucleus)", spin)),
+                            None => {
+                                ui.label("Nuclear spin:");
+                                ui.add(egui::TextEdit::singleline(&mut self.document.nuclear_spin).desired_width(UNCERTAINTY_INPUT_WIDTH))
+                                    .on_hover_text("Nuclear spin I of the isotope, e.g., 5/2. Isotopes with I > 0 show hyperfine structure.")
+                            }
+                        };
+                    }
+                });
+                let mass = isotope_mass(&self.document.scheme_element, &self.document.isotope);
+                if let Err(e) = &mass {
+                    ui.label(RichText::new(e).color(egui::Color32::RED).strong());
+                }
+                ui.add_space(VERTICAL_SPACE);
+
+                // Lower levels and energies of all steps for the suggestions of known levels
+                let steps = self.document.scheme_transitions.len();
+                let lower_energies: Vec<Option<f64>> =
+                    (0..steps).map(|it| lower_energy(self, it)).collect();
+                let energies: Vec<Option<f64>> = (0..steps)
+                    .map(|it| level_energies(self, it + 1).ok().and_then(|e| e[it]))
+                    .collect();
+                let level_table = self.level_tables.get(&element_key);
+                let mismatches = match level_table {
+                    Some(table) => table.check_scheme(self, self.level_tolerance),
+                    None => Vec::new(),
+                };
+                let mismatch_of = |step: Option<usize>| mismatches.iter().find(|m| m.step == step);
+                let mut open_converter: Option<usize> = None;
+
+                // Wavelength, Doppler width, and nuclear spin, shown as tooltip of the steps
+                let isotope_name = format!("{}{:?}", self.document.isotope.trim(), self.document.scheme_element);
+                let spin = nuclear_spin(&self.document.scheme_element, &self.document.isotope, &self.document.nuclear_spin);
+                let temperature = self.document.temperature;
+                let step_info = |it: usize, low_lying: bool| -> Option<String> {
+                    let wavelength = match (low_lying, energies[it], lower_energies[it]) {
+                        (false, Some(upper), Some(lower)) if upper > lower => 1e7 / (upper - lower),
+                        _ => return None,
+                    };
+                    let mut info = format!("Vacuum wavelength: {:.4} nm", wavelength);
+                    if let Ok(mass) = mass {
+                        info.push_str(&format!(
+                            "\nDoppler FWHM at {:.0} K: {:.2} GHz",
+                            temperature,
+                            doppler_fwhm(wavelength, mass, temperature)
+                        ));
+                    }
+                    if let Some(spin) = &spin {
+                        info.push_str(&format!("\nNuclear spin of {}: I = {}", isotope_name, spin));
+                    }
+                    Some(info)
+                };
+
+                // Thermal populations, only shown if the scheme has low-lying states
+                let populations = match self.document.scheme_transitions.iter().any(|t| t.low_lying) {
+                    true => thermal_populations(self, self.document.temperature, level_table).ok(),
+                    false => None,
+                };
+                let max_fraction = populations
+                    .iter()
+                    .flatten()
+                    .filter_map(|p| p.fraction)
+                    .fold(0.0, f64::max);
+                let population_label = |ui: &mut egui::Ui, step: Option<usize>| {
+                    let fraction = populations
+                        .iter()
+                        .flatten()
+                        .find(|p| p.step == step)
+                        .and_then(|p| p.fraction);
+                    match fraction {
+                        Some(f) if f == max_fraction => ui.label(RichText::new(format!("{:.2} %", 100.0 * f)).strong()),
+                        Some(f) => ui.label(format!("{:.2} %", 100.0 * f)),
+                        None => ui.label("–").on_hover_text("Enter the term symbol with J to estimate the population."),
+                    };
+                };
+
                 // Grid with the scheme
                 egui::Grid::new("scheme_grid")
                     .min_col_width(COL_MIN_WIDTH)
@@ -253,55 +1147,153 @@ impl eframe::App for TemplateApp {
                         ui.label("Transition strength (s¯¹)");
                         ui.label("Manifold");
                         ui.label("Properties");
+                        if populations.is_some() {
+                            ui.label("");
+                            ui.label(format!("Population at {:.0} K", self.document.temperature))
+                                .on_hover_text(match level_table {
+                                    Some(_) => "Boltzmann population, with the partition function from the level table.",
+                                    None => "Boltzmann population, with the partition function from the states of the scheme only. Load a level table for a better estimate.",
+                                });
+                        }
                         ui.end_row();
 
-                        ui.label("Ground state (cm¯¹):");
-                        ui.add(
-                            egui::TextEdit::singleline(&mut self.scheme_gs.level)
-                                .desired_width(TEXT_INPUT_WIDTH)
-                                .horizontal_align(egui::Align::RIGHT),
-                        );
-                        ui.add(
-                            egui::TextEdit::singleline(&mut self.scheme_gs.term_symbol)
+                        row_label(ui, RichText::new("Ground state (cm¯¹):"), false, mismatch_of(None), None);
+                        let level_response = ui
+                            .horizontal(|ui| {
+                                value_with_uncertainty(ui, &mut self.document.scheme_gs.level, &mut self.document.scheme_gs.level_unc, 0.0)
+                            })
+                            .inner;
+                        let term_response = ui.add(
+                            egui::TextEdit::singleline(&mut self.document.scheme_gs.term_symbol)
                                 .desired_width(TEXT_INPUT_WIDTH)
                                 .horizontal_align(egui::Align::RIGHT),
                         );
+                        if let Some(table) = level_table {
+                            let gs = &self.document.scheme_gs;
+                            let picked = suggestion_popup(ui, &level_response, "gs_level", || {
+                                table.suggest_levels(&gs.level, &TransitionUnit::CM1, None)
+                            })
+                            .or(suggestion_popup(ui, &term_response, "gs_term", || {
+                                table.suggest_terms(&gs.term_symbol, gs.level.parse().ok(), &TransitionUnit::CM1, None)
+                            }));
+                            if let Some(picked) = picked {
+                                self.document.scheme_gs.level = picked.level;
+                                self.document.scheme_gs.term_symbol = picked.term_symbol;
+                            }
+                        }
+                        if populations.is_some() {
+                            for _ in 0..4 {
+                                ui.label("");
+                            }
+                            population_label(ui, None);
+                        }
                         ui.end_row();
 
-                        for (it, trans) in self.scheme_transitions.iter_mut().enumerate() {
+                        for (it, trans) in self.document.scheme_transitions.iter_mut().enumerate() {
                             let unit = match trans.low_lying {
                                 true => TransitionUnit::CM1.to_string(),
-                                false => self.scheme_unit.to_string(),
+                                false => self.document.scheme_unit.to_string(),
                             };
                             let stp_name = match trans.low_lying {
                                 true => "Low-lying",
                                 false => "Step",
                             };
-                            ui.label(format!("{} {} ({}):", stp_name, it + 1, unit));
-                            ui.add(
-                                egui::TextEdit::singleline(&mut trans.level)
-                                    .desired_width(TEXT_INPUT_WIDTH)
-                                    .horizontal_align(egui::Align::RIGHT),
+                            row_label(
+                                ui,
+                                RichText::new(format!("{} {} ({}):", stp_name, it + 1, unit)),
+                                self.selected_step == Some(it),
+                                mismatch_of(Some(it)),
+                                step_info(it, trans.low_lying),
                             );
-                            ui.add(
+                            let level_response = ui
+                                .horizontal(|ui| value_with_uncertainty(ui, &mut trans.level, &mut trans.level_unc, 0.0))
+                                .inner;
+                            let term_response = ui.add(
                                 egui::TextEdit::singleline(&mut trans.term_symbol)
                                     .desired_width(TEXT_INPUT_WIDTH)
                                     .horizontal_align(egui::Align::RIGHT),
                             );
-                            ui.add(
-                                egui::TextEdit::singleline(&mut trans.transition_strength)
-                                    .desired_width(TEXT_INPUT_WIDTH)
-                                    .horizontal_align(egui::Align::RIGHT),
-                            );
+                            if let Some(table) = level_table {
+                                // low-lying states are always given in cm¯¹
+                                let (unit, lower) = match trans.low_lying {
+                                    true => (&TransitionUnit::CM1, None),
+                                    false => (&self.document.scheme_unit, lower_energies[it]),
+                                };
+                                let picked = suggestion_popup(ui, &level_response, ("level", it), || {
+                                    table.suggest_levels(&trans.level, unit, lower)
+                                })
+                                .or(suggestion_popup(ui, &term_response, ("term", it), || {
+                                    table.suggest_terms(&trans.term_symbol, energies[it], unit, lower)
+                                }));
+                                if let Some(picked) = picked {
+                                    trans.level = picked.level;
+                                    trans.term_symbol = picked.term_symbol;
+                                }
+                            }
+                            ui.horizontal(|ui| {
+                                let mut response = value_with_uncertainty(
+                                    ui,
+                                    &mut trans.transition_strength,
+                                    &mut trans.strength_unc,
+                                    BUTTON_RESERVE_WIDTH,
+                                );
+                                if let Some(input) = &trans.strength_input {
+                                    response = response.on_hover_text(format!("Calculated from {}.", input.description()));
+                                }
+                                if response.changed() {
+                                    trans.strength_input = None;
+                                }
+                                if ui.small_button("…")
+                                    .on_hover_text("Calculate the transition strength from gf, log(gf), f_ik, or the lifetime of the level.")
+                                    .clicked() {
+                                    open_converter = Some(it);
+                                }
+                            });
                             ui.checkbox(&mut trans.low_lying, "Low-lying");
                             ui.checkbox(&mut trans.forbidden, "Forbidden");
+                            let has_details = trans.source.is_some()
+                                || !trans.source_reference.is_empty()
+                                || !trans.note.is_empty();
+                            let details_label = match has_details {
+                                true => RichText::new("Details").strong(),
+                                false => RichText::new("Details"),
+                            };
+                            ui.toggle_value(&mut self.expanded_steps[it], details_label)
+                                .on_hover_text("Source of the values, reference, and a note for this step.");
+                            if populations.is_some() && trans.low_lying {
+                                population_label(ui, Some(it));
+                            }
                             ui.end_row();
+
+                            // Details of the step
+                            if self.expanded_steps[it] {
+                                ui.label("");
+                                egui::ComboBox::from_id_source(("step_source", it))
+                                    .selected_text(trans.source.map_or("Source".to_string(), |s| s.to_string()))
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut trans.source, None, "Not given");
+                                        for source in StepSource::iter() {
+                                            ui.selectable_value(&mut trans.source, Some(source), source.to_string());
+                                        }
+                                    });
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut trans.source_reference)
+                                        .desired_width(TEXT_INPUT_WIDTH)
+                                        .hint_text("DOI, URL, or reference"),
+                                );
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut trans.note)
+                                        .desired_width(TEXT_INPUT_WIDTH)
+                                        .hint_text("Note"),
+                                );
+                                ui.end_row();
+                            }
                         }
 
                         ui.label("IP (cm¯¹):");
-                        ui.label(format!("{0:.3}", self.scheme_element.ip()));
+                        ui.label(format!("{0:.3}", self.document.scheme_element.ip()));
                         ui.add(
-                            egui::TextEdit::singleline(&mut self.scheme_ip_term_symbol)
+                            egui::TextEdit::singleline(&mut self.document.scheme_ip_term_symbol)
                                 .desired_width(TEXT_INPUT_WIDTH)
                                 .horizontal_align(egui::Align::RIGHT),
                         );
@@ -309,8 +1301,114 @@ impl eframe::App for TemplateApp {
                     });
                 ui.add_space(VERTICAL_SPACE);
 
+                // Converter of transition strengths
+                if let Some(it) = open_converter {
+                    self.strength_converter = Some(match &self.document.scheme_transitions[it].strength_input {
+                        Some(input) => StrengthConverter::new(it, input.clone(), true),
+                        None => StrengthConverter::new(it, step_input(self, it), false),
+                    });
+                }
+                let mut converted: Option<(usize, f64, StrengthInput)> = None;
+                if let Some(conv) = &mut self.strength_converter {
+                    let mut open = true;
+                    egui::Window::new(format!("Transition strength of step {}", conv.step + 1))
+                        .open(&mut open)
+                        .collapsible(false)
+                        .resizable(false)
+                        .show(ui.ctx(), |ui| {
+                            egui::Grid::new("strength_converter_grid").show(ui, |ui| {
+                                ui.label("Quantity:");
+                                egui::ComboBox::from_id_source("strength_quantity")
+                                    .selected_text(conv.quantity.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for quantity in StrengthQuantity::iter() {
+                                            let label = quantity.to_string();
+                                            ui.selectable_value(&mut conv.quantity, quantity, label);
+                                        }
+                                    });
+                                ui.end_row();
+                                ui.label("Value:");
+                                ui.text_edit_singleline(&mut conv.value);
+                                ui.end_row();
+                                match conv.quantity {
+                                    StrengthQuantity::Lifetime => {
+                                        ui.label("Branching ratio:")
+                                            .on_hover_text("Fraction of decays of the upper level into the lower level. Leave empty for 1.");
+                                        ui.text_edit_singleline(&mut conv.branching_ratio);
+                                        ui.end_row();
+                                    }
+                                    _ => {
+                                        ui.label("Vacuum wavelength (nm):");
+                                        ui.text_edit_singleline(&mut conv.wavelength);
+                                        ui.end_row();
+                                        ui.label("J of the lower level:");
+                                        ui.text_edit_singleline(&mut conv.j_lower);
+                                        ui.end_row();
+                                        ui.label("J of the upper level:");
+                                        ui.text_edit_singleline(&mut conv.j_upper);
+                                        ui.end_row();
+                                    }
+                                }
+                            });
+                            ui.label("The wavelength and J values are taken from the scheme if available.");
+                            if ui.button("Apply").clicked() {
+                                match conv.input().and_then(|input| Ok((input.einstein_a()?, input))) {
+                                    Ok((a, input)) => converted = Some((conv.step, a, input)),
+                                    Err(e) => conv.error = e,
+                                }
+                            }
+                            if !conv.error.is_empty() {
+                                ui.label(RichText::new(&conv.error).color(egui::Color32::RED).strong());
+                            }
+                        });
+                    if !open {
+                        self.strength_converter = None;
+                    }
+                }
+                if let Some((it, a, input)) = converted {
+                    self.document.scheme_transitions[it].transition_strength = format!("{:.3e}", a);
+                    self.document.scheme_transitions[it].strength_input = Some(input);
+                    self.strength_converter = None;
+                }
+
+                // Check of the levels against the level table
+                if level_table.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.label("Tolerance for known levels (cm¯¹):");
+                        ui.add(
+                            egui::DragValue::new(&mut self.level_tolerance)
+                                .speed(0.1)
+                                .clamp_range(0.0..=1000.0),
+                        )
+                        .on_hover_text("Levels that differ by more than this from all levels in the level table are flagged.");
+                    });
+                    let mut fix: Option<LevelMismatch> = None;
+                    for mismatch in mismatches.iter() {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&mismatch.message).color(ui.visuals().warn_fg_color));
+                            if ui.button("Fix").on_hover_text("Replace the entry with the known level.").clicked() {
+                                fix = Some(mismatch.clone());
+                            }
+                        });
+                    }
+                    if let Some(fix) = fix {
+                        let (level, term_symbol) = match fix.step {
+                            Some(it) => (
+                                &mut self.document.scheme_transitions[it].level,
+                                &mut self.document.scheme_transitions[it].term_symbol,
+                            ),
+                            None => (&mut self.document.scheme_gs.level, &mut self.document.scheme_gs.term_symbol),
+                        };
+                        if let Some(new_level) = fix.level {
+                            *level = new_level;
+                        }
+                        *term_symbol = fix.term_symbol;
+                    }
+                    ui.add_space(VERTICAL_SPACE);
+                }
+
                 // Last step to IP
-                ui.checkbox(&mut self.scheme_last_step_to_ip, "Unknown ionization step?")
+                ui.checkbox(&mut self.document.scheme_last_step_to_ip, "Unknown ionization step?")
                     .on_hover_text("Check this box if the ionization step is unknown and the last transition should be drawn to the ionization potential with a hollow arrow.");
                 ui.add_space(VERTICAL_SPACE);
 
@@ -318,20 +1416,20 @@ impl eframe::App for TemplateApp {
                 ui.horizontal(|ui| {
                     ui.label("Lasers:");
                     egui::ComboBox::from_id_source("Lasers")
-                        .selected_text(self.scheme_lasers.to_string())
+                        .selected_text(self.document.scheme_lasers.to_string())
                         .show_ui(ui, |ui| {
                             ui.selectable_value(
-                                &mut self.scheme_lasers,
+                                &mut self.document.scheme_lasers,
                                 Lasers::TiSa,
                                 Lasers::TiSa.to_string(),
                             );
                             ui.selectable_value(
-                                &mut self.scheme_lasers,
+                                &mut self.document.scheme_lasers,
                                 Lasers::Dye,
                                 Lasers::Dye.to_string(),
                             );
                             ui.selectable_value(
-                                &mut self.scheme_lasers,
+                                &mut self.document.scheme_lasers,
                                 Lasers::Both,
                                 Lasers::Both.to_string(),
                             );
@@ -406,6 +1504,24 @@ impl eframe::App for TemplateApp {
                         ui.add(egui::TextEdit::singleline(&mut self.sat_tmp_ydat_unc)
                             .desired_width(TEXT_INPUT_WIDTH));
                         ui.end_row();
+
+                        ui.label("Repetition rate (kHz)");
+                        ui.add(egui::TextEdit::singleline(&mut self.sat_tmp_repetition_rate)
+                            .desired_width(TEXT_INPUT_WIDTH)
+                            .hint_text("optional"));
+
+                        ui.label("Wavelength (nm)");
+                        ui.add(egui::TextEdit::singleline(&mut self.sat_tmp_wavelength)
+                            .desired_width(TEXT_INPUT_WIDTH)
+                            .hint_text("optional"));
+                        ui.end_row();
+
+                        ui.label("Beam diameter (mm)")
+                            .on_hover_text("Diameter of a top-hat beam, used to convert between power and irradiance.");
+                        ui.add(egui::TextEdit::singleline(&mut self.sat_tmp_beam_diameter)
+                            .desired_width(TEXT_INPUT_WIDTH)
+                            .hint_text("optional"));
+                        ui.end_row();
                     });
                 ui.add_space(VERTICAL_SPACE);
 
@@ -423,12 +1539,17 @@ impl eframe::App for TemplateApp {
                         if self.error_saturation.is_empty() {
                             // check if entry already exists
                             let mut index_exists: Option<usize> = None;
-                            for (index, entry) in self.saturation_curves.clone().iter().enumerate() {
+                            for (index, entry) in self.document.saturation_curves.clone().iter().enumerate() {
                                 if entry.title.eq(&self.sat_tmp_title) {
                                     index_exists = Some(index);
                                     break;
                                 }
                             }
+                            let laser = LaserParameters::from_parts(
+                                &self.sat_tmp_repetition_rate,
+                                &self.sat_tmp_wavelength,
+                                &self.sat_tmp_beam_diameter,
+                            );
                             match SaturationCurve::new_from_parts(
                                 &self.sat_tmp_title,
                                 &self.sat_tmp_notes,
@@ -438,11 +1559,11 @@ impl eframe::App for TemplateApp {
                                 &self.sat_tmp_xdat_unc,
                                 &self.sat_tmp_ydat,
                                 &self.sat_tmp_ydat_unc,
-                            ) {
+                            ).and_then(|sc| Ok(SaturationCurve { laser: laser?, ..sc })) {
                                 Ok(sc) => {
                                     match index_exists {
-                                        Some(index) => self.saturation_curves[index] = sc,
-                                        None => self.saturation_curves.push(sc),
+                                        Some(index) => self.document.saturation_curves[index] = sc,
+                                        None => self.document.saturation_curves.push(sc),
                                     };
                                     self.sat_tmp_title.clear();
                                     self.sat_tmp_notes.clear();
@@ -451,6 +1572,9 @@ impl eframe::App for TemplateApp {
                                     self.sat_tmp_xdat_unc.clear();
                                     self.sat_tmp_ydat.clear();
                                     self.sat_tmp_ydat_unc.clear();
+                                    self.sat_tmp_repetition_rate.clear();
+                                    self.sat_tmp_wavelength.clear();
+                                    self.sat_tmp_beam_diameter.clear();
                                 }
                                 Err(err) => self.error_saturation = err,
                             };
@@ -467,22 +1591,22 @@ impl eframe::App for TemplateApp {
                 ui.add_space(VERTICAL_SPACE);
 
                 // Grid view of existing saturation curve
-                if !self.saturation_curves.is_empty() {
+                if !self.document.saturation_curves.is_empty() {
                     ui.label("Sorted list of existing saturation curve entries:");
                     ui.add_space(VERTICAL_SPACE);
 
                     egui::Grid::new("saturation_curve_table")
                         .striped(true)
                         .show(ui, |ui| {
-                            for (it, val) in self.saturation_curves.clone().iter().enumerate() {
+                            for (it, val) in self.document.saturation_curves.clone().iter().enumerate() {
                                 ui.label(&val.title);
 
                                 // Move up and down buttons
                                 if ui.button("Move up").clicked() && it > 0 {
-                                    self.saturation_curves.swap(it, it - 1);
+                                    self.swap_saturation_curves(it, it - 1);
                                 }
-                                if ui.button("Move down").clicked() && it < self.saturation_curves.len() - 1 {
-                                    self.saturation_curves.swap(it, it + 1);
+                                if ui.button("Move down").clicked() && it < self.document.saturation_curves.len() - 1 {
+                                    self.swap_saturation_curves(it, it + 1);
                                 }
 
                                 // Edit button
@@ -495,16 +1619,57 @@ impl eframe::App for TemplateApp {
                                     self.sat_tmp_xdat_unc = val.get_xdat_unc();
                                     self.sat_tmp_ydat = val.get_ydat();
                                     self.sat_tmp_ydat_unc = val.get_ydat_unc();
+                                    let text = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+                                    self.sat_tmp_repetition_rate = text(val.laser.repetition_rate);
+                                    self.sat_tmp_wavelength = text(val.laser.wavelength);
+                                    self.sat_tmp_beam_diameter = text(val.laser.beam_diameter);
                                 }
 
                                 // Delete button
                                 if ui.button("Delete").clicked() {
-                                    self.saturation_curves.remove(it);
+                                    self.document.saturation_curves.remove(it);
+                                    self.sat_detail = match self.sat_detail {
+                                        Some(detail) if detail > it => Some(detail - 1),
+                                        Some(detail) if detail < it => Some(detail),
+                                        _ => None,
+                                    };
+                                }
+
+                                // Details of the fit
+                                let mut shown = self.sat_detail == Some(it);
+                                if ui.toggle_value(&mut shown, "Fit details").clicked() {
+                                    self.sat_detail = if shown { Some(it) } else { None };
+                                }
+
+                                // Selection for the comparison
+                                let mut compared = self.sat_compare.contains(&val.title);
+                                if ui.checkbox(&mut compared, "Compare").changed() {
+                                    match compared {
+                                        true => self.sat_compare.push(val.title.clone()),
+                                        false => self.sat_compare.retain(|t| t != &val.title),
+                                    }
                                 }
                                 ui.end_row();
                             }
                         });
                     ui.add_space(VERTICAL_SPACE);
+
+                    if let Some(curve) = self.sat_detail.and_then(|it| self.document.saturation_curves.get(it)) {
+                        saturation_details(ui, curve);
+                        ui.add_space(VERTICAL_SPACE);
+                    }
+
+                    // Compared curves in the order of the list
+                    let compared: Vec<&SaturationCurve> = self
+                        .document
+                        .saturation_curves
+                        .iter()
+                        .filter(|c| self.sat_compare.contains(&c.title))
+                        .collect();
+                    if !compared.is_empty() {
+                        saturation_comparison(ui, &compared);
+                        ui.add_space(VERTICAL_SPACE);
+                    }
                 };
 
                 ui.separator();
@@ -532,6 +1697,30 @@ impl eframe::App for TemplateApp {
                         ui.end_row();
                         ui.label("Enter year (4 digits):");
                         ui.text_edit_singleline(&mut self.reference_year);
+                        ui.end_row();
+                        ui.label("Kind of reference:");
+                        egui::ComboBox::from_id_source("ReferenceKind")
+                            .selected_text(self.reference_kind.to_string())
+                            .show_ui(ui, |ui| {
+                                for kind in ReferenceKind::iter() {
+                                    let tmp_label = kind.to_string();
+                                    ui.selectable_value(&mut self.reference_kind, kind, tmp_label);
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("Title (optional):");
+                        ui.text_edit_singleline(&mut self.reference_title);
+                        ui.end_row();
+                        ui.label("Journal (optional):");
+                        ui.text_edit_singleline(&mut self.reference_journal)
+                            .on_hover_text("Journal, proceedings, university for theses, or repository for datasets.");
+                        ui.end_row();
+                        ui.label("Volume (optional):");
+                        ui.text_edit_singleline(&mut self.reference_volume);
+                        ui.end_row();
+                        ui.label("Pages (optional):");
+                        ui.text_edit_singleline(&mut self.reference_pages)
+                            .on_hover_text("Page range or article number, e.g., 123-130.");
                     });
                 ui.add_space(VERTICAL_SPACE);
 
@@ -541,47 +1730,170 @@ impl eframe::App for TemplateApp {
                     .on_hover_text("Add the current reference to the list.")
                     .clicked()
                 {
-                    let mut index_exists: Option<usize> = None;
-                    for (index, entry) in self.references.clone().iter().enumerate() {
-                        if entry.id.eq(&self.reference_id) {
-                            index_exists = Some(index);
-                            break;
-                        }
-                    }
-
                     let mut reference_to_write: Option<ReferenceEntry> = None;
 
                     if !self.reference_id.is_empty() {
-                        if self.reference_author.is_empty() || self.reference_year.is_empty() {  // so we have a doi
-                            if is_doi(&self.reference_id) {
-                                reference_to_write = Some(ReferenceEntry::new_from_doi(&self.reference_id));
-                            } else {
-                                self.error_reference = "This does not look like a DOI. If that is intentional, please fill in the Author and Year data.".into();
+                        match self.reference_id.parse::<ReferenceId>() {
+                            Ok(rid) => {
+                                if self.reference_author.is_empty() || self.reference_year.is_empty() {  // so we need a doi
+                                    match rid {
+                                        ReferenceId::Doi(doi) => reference_to_write = Some(ReferenceEntry::new_from_doi(&doi)),
+                                        ReferenceId::Url(_) => self.error_reference = "This does not look like a DOI. If that is intentional, please fill in the Author and Year data.".into(),
+                                    };
+                                } else {  // so we have entries everywhere
+                                    match self.reference_year.parse::<usize>() {
+                                        Ok(year) => reference_to_write = Some(ReferenceEntry::new_from_url(rid.as_str(), &self.reference_author, year)),
+                                        Err(_) => self.error_reference = "Cannot parse year. Please check it is a number.".into(),
+                                    };
+                                };
                             }
-                        } else {  // so we have an URL with entries everywhere
-                            match self.reference_year.parse::<usize>() {
-                                Ok(year) => reference_to_write = Some(ReferenceEntry::new_from_url(&self.reference_id, &self.reference_author, year)),
-                                Err(_) => self.error_reference = "Cannot parse year. Please check it is a number.".into(),
-                            };
+                            Err(e) => self.error_reference = e,
                         };
                     } else {
                         self.error_reference = "Reference is empty".into();
                     };
 
                     if let Some(entry) = reference_to_write {
+                        let entry = entry.with_details(
+                            &self.reference_title,
+                            &self.reference_journal,
+                            &self.reference_volume,
+                            &self.reference_pages,
+                            self.reference_kind.clone(),
+                        );
+                        let index_exists = self.document.references.iter().position(|r| r.id.eq(&entry.id));
                         match index_exists {
-                            Some(index) => self.references[index] = entry,
-                            None => self.references.push(entry)
+                            Some(index) => self.document.references[index] = entry,
+                            None => self.document.references.push(entry)
                         };
                         self.reference_id.clear();
                         self.reference_author.clear();
                         self.reference_year.clear();
+                        self.reference_title.clear();
+                        self.reference_journal.clear();
+                        self.reference_volume.clear();
+                        self.reference_pages.clear();
+                        self.reference_kind = ReferenceKind::Article;
                         self.error_reference.clear();
                     }
 
                 };
                 ui.add_space(VERTICAL_SPACE);
 
+                // Import references from a bibliography file
+                ui.horizontal(|ui| {
+                    if ui.button("Import BibTeX / RIS file")
+                        .on_hover_text("Select a .bib or .ris file, e.g., exported from Zotero, to add all its references.")
+                        .clicked() {
+                        let sender = self.bibliography_channel.0.clone();
+                        let filter = ["bib", "ris"];
+                        let task = rfd::AsyncFileDialog::new()
+                            .add_filter("BibTeX or RIS file", &filter)
+                            .pick_file();
+                        let ctx = ui.ctx().clone();
+                        execute(async move {
+                            let file = task.await;
+                            if let Some(file) = file {
+                                let text = file.read().await;
+                                let _ = sender.send(String::from_utf8_lossy(&text).to_string());
+                                ctx.request_repaint();
+                            }
+                        });
+                    }
+                    if !self.info_reference_import.is_empty() {
+                        ui.label(&self.info_reference_import);
+                    }
+                });
+
+                // deal with uploaded bibliography file
+                if let Ok(text) = self.bibliography_channel.1.try_recv() {
+                    self.error_reference.clear();
+                    self.info_reference_import.clear();
+                    match parse_bibliography(&text) {
+                        Ok(import) => {
+                            let found = import.entries.len();
+                            let added = merge_references(&mut self.document.references, import.entries);
+                            self.history_changed = true;
+                            self.info_reference_import = format!(
+                                "Imported {} reference(s), skipped {} duplicate(s) and {} without DOI or URL, author, and year.",
+                                added,
+                                found - added,
+                                import.unusable
+                            );
+                        }
+                        Err(e) => self.error_reference = e,
+                    };
+                }
+                ui.add_space(VERTICAL_SPACE);
+
+                // Resolve DOI metadata
+                ui.horizontal(|ui| {
+                    if ui.button("Load DOI cache file")
+                        .on_hover_text("Select a CSL-JSON file, e.g., exported from Zotero, with the metadata of your DOIs.")
+                        .clicked() {
+                        let sender = self.doi_cache_channel.0.clone();
+                        let filter = ["json"];
+                        let task = rfd::AsyncFileDialog::new()
+                            .add_filter("CSL-JSON file", &filter)
+                            .pick_file();
+                        let ctx = ui.ctx().clone();
+                        execute(async move {
+                            let file = task.await;
+                            if let Some(file) = file {
+                                let text = file.read().await;
+                                let _ = sender.send(String::from_utf8_lossy(&text).to_string());
+                                ctx.request_repaint();
+                            }
+                        });
+                    }
+                    if ui.add_enabled(self.doi_pending == 0, egui::Button::new("Resolve DOI metadata"))
+                        .on_hover_text("Fill in author, year, and title of all DOI references.")
+                        .clicked() {
+                        self.doi_resolved = 0;
+                        self.resolve_dois(ui.ctx());
+                    }
+                    if self.doi_pending > 0 {
+                        ui.spinner();
+                    }
+                    if !self.info_doi_resolution.is_empty() {
+                        ui.label(&self.info_doi_resolution);
+                    }
+                });
+
+                // deal with uploaded DOI cache file
+                if let Ok(text) = self.doi_cache_channel.1.try_recv() {
+                    self.error_reference.clear();
+                    match CacheResolver::from_json(&text) {
+                        Ok(cache) => {
+                            self.doi_cache = Some(Arc::new(cache));
+                            self.doi_resolved = 0;
+                            self.resolve_dois(ui.ctx());
+                        }
+                        Err(e) => self.error_reference = e,
+                    };
+                }
+
+                // deal with resolved DOI metadata
+                while let Ok((doi, result)) = self.doi_channel.1.try_recv() {
+                    self.doi_pending = self.doi_pending.saturating_sub(1);
+                    match result {
+                        Ok(Some(meta)) => {
+                            if let Some(entry) = self.document.references.iter_mut().find(|r| r.id == doi) {
+                                entry.apply_doi_metadata(&meta);
+                                self.doi_resolved += 1;
+                                self.history_changed = true;
+                            }
+                        }
+                        Ok(None) => (),
+                        Err(e) => self.error_reference = e,
+                    };
+                    self.info_doi_resolution = match self.doi_pending {
+                        0 => format!("Resolved {} DOI reference(s).", self.doi_resolved),
+                        n => format!("Resolved {} DOI reference(s), {} pending.", self.doi_resolved, n),
+                    };
+                }
+                ui.add_space(VERTICAL_SPACE);
+
                 ui.label(
                     RichText::new(&self.error_reference)
                         .color(egui::Color32::RED)
@@ -589,21 +1901,17 @@ impl eframe::App for TemplateApp {
                 );
                 ui.add_space(VERTICAL_SPACE);
 
-                if !self.references.is_empty() {
+                if !self.document.references.is_empty() {
                     ui.label("List of existing references:");
                     ui.add_space(VERTICAL_SPACE);
 
                     egui::Grid::new("reference_table")
                         .striped(true)
                         .show(ui, |ui| {
-                            for (it, val) in self.references.clone().iter().enumerate() {
+                            for (it, val) in self.document.references.clone().iter().enumerate() {
                                 // Label
-                                let lbl_hover_text = if val.year == 0 && val.author.is_empty() {
-                                    format!("https://doi.org/{}", val.id)
-                                } else {
-                                    format!("{} ({})", val.author, val.year)
-                                };
-                                ui.label(&val.id).on_hover_text(lbl_hover_text);
+                                ui.label(&val.id).on_hover_text(val.full_citation());
+                                ui.label(val.short_citation());
 
                                 // Check URL button
                                 if ui.button("Open URL").clicked() {
@@ -616,10 +1924,10 @@ impl eframe::App for TemplateApp {
 
                                 // Move up and down buttons
                                 if ui.button("Move up").clicked() && it > 0 {
-                                    self.references.swap(it, it - 1);
+                                    self.document.references.swap(it, it - 1);
                                 }
-                                if ui.button("Move down").clicked() && it < self.references.len() - 1 {
-                                    self.references.swap(it, it + 1);
+                                if ui.button("Move down").clicked() && it < self.document.references.len() - 1 {
+                                    self.document.references.swap(it, it + 1);
                                 }
 
                                 // Edit button
@@ -630,16 +1938,39 @@ impl eframe::App for TemplateApp {
                                         0 => String::new(),
                                         _ => val.year.to_string(),
                                     };
+                                    self.reference_title = val.title.clone().unwrap_or_default();
+                                    self.reference_journal = val.journal.clone().unwrap_or_default();
+                                    self.reference_volume = val.volume.clone().unwrap_or_default();
+                                    self.reference_pages = val.pages.clone().unwrap_or_default();
+                                    self.reference_kind = val.kind.clone();
                                 }
 
                                 // Delete button
                                 if ui.button("Delete").clicked() {
-                                    self.references.remove(it);
+                                    self.document.references.remove(it);
                                 }
                                 ui.end_row();
                             }
                         });
                     ui.add_space(VERTICAL_SPACE);
+
+                    if ui.button("Download references (Markdown)")
+                        .on_hover_text("Save the formatted list of references as a Markdown file.")
+                        .clicked() {
+                        let filter = ["md"];
+                        let task = rfd::AsyncFileDialog::new()
+                            .set_file_name("references.md")
+                            .add_filter("Markdown file", &filter)
+                            .save_file();
+                        let contents = create_references_markdown(&self.document.references);
+                        execute(async move {
+                            let file = task.await;
+                            if let Some(file) = file {
+                                _ = file.write(contents.as_bytes()).await
+                            }
+                        });
+                    }
+                    ui.add_space(VERTICAL_SPACE);
                 };
 
                 ui.separator();
@@ -672,7 +2003,7 @@ impl eframe::App for TemplateApp {
                             self.error_submission = format!("Error creating JSON output: {}", e);
                             "".to_owned()
                         });
-                        let url = create_gh_issue(&body, &self.scheme_element);
+                        let url = create_gh_issue(&body, &self.document.scheme_element);
                         let open_url = egui::OpenUrl {
                             url,
                             new_tab: true,
@@ -689,7 +2020,7 @@ impl eframe::App for TemplateApp {
                             self.error_submission = format!("Error creating JSON output: {}", e);
                             "".to_owned()
                         });
-                        let url = create_email_link(&body, &self.scheme_element);
+                        let url = create_email_link(&body, &self.document.scheme_element);
                         let open_url = egui::OpenUrl{
                             url,
                             new_tab: true,
@@ -707,7 +2038,32 @@ impl eframe::App for TemplateApp {
                         if !body.is_empty() {
                             let filter = ["json"];
                             let task = rfd::AsyncFileDialog::new()
-                                .set_file_name(format!("{:?}.json", self.scheme_element))
+                                .set_file_name(format!("{:?}.json", self.document.scheme_element))
+                                .add_filter("JSON file", &filter)
+                                .save_file();
+                            let contents = body;
+                            execute(async move {
+                                let file = task.await;
+                                if let Some(file) = file {
+                                    _ = file.write(contents.as_bytes()).await
+                                }
+                            });
+                        }
+                    }
+
+                    if ui
+                        .button("Download RIMSSchemeDrawer file")
+                        .on_hover_text("Download the scheme as a configuration file that RIMSSchemeDrawer can open directly.")
+                        .clicked() {
+                        self.error_submission.clear();
+                        let body = create_drawer_output(self).unwrap_or_else(|e| {
+                            self.error_submission = format!("Error creating JSON output: {}", e);
+                            "".to_owned()
+                        });
+                        if !body.is_empty() {
+                            let filter = ["json"];
+                            let task = rfd::AsyncFileDialog::new()
+                                .set_file_name(format!("{:?}_rimsschemedrawer.json", self.document.scheme_element))
                                 .add_filter("JSON file", &filter)
                                 .save_file();
                             let contents = body;
@@ -730,8 +2086,12 @@ impl eframe::App for TemplateApp {
                         println!("{}", body);
                     }
 
-                    if ui.add(egui::Button::new("Clear all")).clicked() {
-                        *self = Default::default();
+                    if ui
+                        .add(egui::Button::new("Clear all"))
+                        .on_hover_text("Clear the scheme of the current tab.")
+                        .clicked()
+                    {
+                        self.confirm_clear = true;
                     }
                     if !self.error_submission.is_empty() {
                         ui.label(
@@ -752,6 +2112,32 @@ impl eframe::App for TemplateApp {
                 });
             });
         });
+
+        if self.confirm_clear {
+            egui::Window::new("Clear all")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    ui.label("Clear the scheme of the current tab?");
+                    ui.label(format!(
+                        "This can be undone with {}.",
+                        ctx.format_shortcut(&UNDO_SHORTCUT)
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Clear").clicked() {
+                            self.clear_document();
+                            self.reset_document_view();
+                            self.confirm_clear = false;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.confirm_clear = false;
+                        }
+                    });
+                });
+        }
+
+        self.record_history(ctx);
     }
 
     /// Called by the framework to save state before shutdown.
@@ -800,10 +2186,24 @@ It should contain at a minimum the element, the ground state, as well as one or
 First select the units that you would like to use (nm or cm¯¹). Then fill out the \
 levels, optional term symbols, transmission strengths (in s¯¹), \
 and whether the level is a low-lying level or if the transition is forbidden. \
+Uncertainties can be given in the fields next to the levels and transition strengths, \
+or directly in concise notation, e.g., \"25123.45(3)\". \
+Transition strengths can also be calculated from gf, log(gf), f_ik, or a lifetime \
+with the button next to the cell; the input is then kept in the export. \
+For low-lying states, the thermal populations at the atomizer temperature are shown \
+next to the rows (J is taken from the term symbols). \
+Hover over a step to see its vacuum wavelength and Doppler width at this temperature, \
+calculated with the mass of the selected isotope or the standard atomic weight. \
+Use \"Details\" to state where the values of a step come from, with a reference and a short note. \
 Simple term symbols can be entered directly, e.g., \"3F2\" will render to ³F₂. \
 More complicated term symbols can be entered in LaTeX formatting, i.e., \"5d^{2}5s^{2} (^{3}F_{2})\". \
 Finally, select the lasers that were used for this scheme. \
-Further information can always be provided in the notes.";
+Further information can always be provided in the notes.\n\
+The diagram next to the form shows the scheme as you type. Click a level to highlight its row, \
+drag labels to move them, and right-click a level to toggle its properties or to insert a step.\n\
+If you load a level table of the element (CSV export of the NIST Atomic Spectra Database), \
+known levels are suggested while you type a level or term symbol. Picking one fills in both. \
+Levels and term symbols that do not match the table within the tolerance are flagged.";
 
 const USAGE_MESSAGE_SATURATION: &str = "To add a saturation curve, you need to add at least \
 a title, the unit used for the x-values, x- and y-data. Optionally, you can provide notes and \
@@ -814,11 +2214,24 @@ If you choose units in Watts, please make sure that you include the approximate
 laser is included in the notes. \
 Finally, data can be pasted, e.g., from Excel, into the individual field. \
 Each field needs to contain the same number of values. \
-Values can be separated by comma, semicolon, or space.";
+Values can be separated by comma, semicolon, or space.\n\
+With the repetition rate, wavelength, and beam diameter of the laser, the saturation fluence, \
+the cross-section, and the power for 90% saturation are derived from the fit (see \"Fit details\") \
+and included in the submission.\n\
+Select \"Compare\" for several curves to overlay them on one plot, normalized to their fitted \
+amplitudes, and to see their fit parameters side by side, e.g., to pick the operating point of \
+each step.";
 
 const USAGE_MESSAGE_REFERENCE: &str = "You can either provide only a `doi` (leaving the author and year fields empty) \
 or you can provide a URL to an article as well as an author name and year. \
-Please provide the author name in the same way as it should be displayed, e.g., \"Chrysalidis et al.\".";
+Please provide the author name in the same way as it should be displayed, e.g., \"Chrysalidis et al.\". \
+DOIs can also be entered as links (e.g., https://doi.org/10.1000/xyz) or with a `doi:` prefix, \
+they are stored in their short, lowercase form.\n\
+Author, year, and title of DOI references can be filled in automatically from a DOI cache file \
+in CSL-JSON format (e.g., exported from Zotero). Hover over a reference to see its full citation.\n\
+You can also import all references from a BibTeX (.bib) or RIS (.ris) file, e.g., exported from Zotero. \
+Entries with a DOI are added by DOI, all others by URL, first author, and year. \
+References that are already in the list are skipped.";
 
 const USAGE_MESSAGE_SUBMISSION: &str = "If you have a GitHub account, please press the \"Submit via GitHub\" button. \n \
 Otherwise, you can submit via e-mail. If you have an e-mail client configured on your system, press the \"Submit via E-Mail\" button. \n \
@@ -826,5 +2239,21 @@ Alternatively or if both options above fail, you can download the configuration
 A list of current maintainers can be found on the following website:";
 
 const COL_MIN_WIDTH: f32 = 120.0;
+const SUGGESTION_MIN_WIDTH: f32 = 320.0;
+const DIAGRAM_DEFAULT_WIDTH: f32 = 420.0;
+const PNG_EXPORT_SCALE: f32 = 2.0;
+const COMPARISON_MAX_WIDTH: f32 = 600.0;
+const COMPARISON_HEIGHT: f32 = 300.0;
 const TEXT_INPUT_WIDTH: f32 = f32::INFINITY;
+const UNCERTAINTY_INPUT_WIDTH: f32 = 50.0;
+/// Width of the ± sign including the spacing around it.
+const PLUS_MINUS_WIDTH: f32 = 30.0;
+/// Width of a small button next to an input, including the spacing.
+const BUTTON_RESERVE_WIDTH: f32 = 30.0;
 const VERTICAL_SPACE: f32 = 12.0;
+const UNDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+const REDO_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::Z,
+);
@@ -0,0 +1,360 @@
+//! Batch validation of a whole directory tree of database entries.
+//!
+//! Every JSON file is loaded through the submission parser and checked with the same validators
+//! as a single submission. The resulting report can be written as plain text, JSON, or JUnit XML,
+//! such that the database repository can gate merges on it.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use strum::IntoEnumIterator;
+
+use crate::{create_json_output, load_submission, Elements, TemplateApp};
+
+/// Validation result of a single file.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub element: Option<String>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Validation result of all files in a directory.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct BatchReport {
+    pub files: Vec<FileReport>,
+    /// Number of valid schemes per element.
+    pub coverage: BTreeMap<String, usize>,
+    /// Groups of files that describe the same scheme.
+    pub duplicates: Vec<Vec<String>>,
+}
+
+/// Output formats of the batch report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Junit,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "junit" => Ok(ReportFormat::Junit),
+            _ => Err(format!(
+                "Unknown report format '{}', use 'text', 'json', or 'junit'.",
+                s
+            )),
+        }
+    }
+}
+
+impl BatchReport {
+    /// Number of files with at least one error.
+    pub fn failed_files(&self) -> usize {
+        self.files.iter().filter(|f| !f.errors.is_empty()).count()
+    }
+
+    /// True if any file has an error or if duplicates were found.
+    pub fn has_errors(&self) -> bool {
+        self.failed_files() > 0 || !self.duplicates.is_empty()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_string(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+            ReportFormat::Junit => self.to_junit(),
+        }
+    }
+
+    /// Create a JUnit XML report with one test case per file and one for the duplicates.
+    fn to_junit(&self) -> String {
+        let tests = self.files.len() + 1;
+        let failures = self.failed_files() + usize::from(!self.duplicates.is_empty());
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"scheme-submission\" tests=\"{}\" failures=\"{}\">\n",
+            tests, failures
+        ));
+        xml.push_str(&format!(
+            "  <testsuite name=\"database\" tests=\"{}\" failures=\"{}\">\n",
+            tests, failures
+        ));
+
+        for file in self.files.iter() {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\">\n",
+                xml_escape(&file.path),
+                xml_escape(file.element.as_deref().unwrap_or("unknown"))
+            ));
+            for error in file.errors.iter() {
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\"/>\n",
+                    xml_escape(error)
+                ));
+            }
+            if !file.warnings.is_empty() {
+                xml.push_str(&format!(
+                    "      <system-out>{}</system-out>\n",
+                    xml_escape(&file.warnings.join("\n"))
+                ));
+            }
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("    <testcase name=\"duplicates\" classname=\"database\">\n");
+        for group in self.duplicates.iter() {
+            xml.push_str(&format!(
+                "      <failure message=\"Duplicate schemes: {}\"/>\n",
+                xml_escape(&group.join(", "))
+            ));
+        }
+        xml.push_str("    </testcase>\n");
+
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for file in self.files.iter() {
+            let status = match file.errors.is_empty() {
+                true => "OK   ",
+                false => "ERROR",
+            };
+            writeln!(f, "{} {}", status, file.path)?;
+            for error in file.errors.iter() {
+                writeln!(f, "      error: {}", error)?;
+            }
+            for warning in file.warnings.iter() {
+                writeln!(f, "      warning: {}", warning)?;
+            }
+        }
+
+        writeln!(f)?;
+        for group in self.duplicates.iter() {
+            writeln!(f, "Duplicate schemes: {}", group.join(", "))?;
+        }
+
+        let covered: Vec<String> = self
+            .coverage
+            .iter()
+            .map(|(el, n)| format!("{} ({})", el, n))
+            .collect();
+        writeln!(
+            f,
+            "Element coverage: {} of {} elements: {}",
+            self.coverage.len(),
+            Elements::iter().count(),
+            covered.join(", ")
+        )?;
+        write!(
+            f,
+            "{} file(s) checked, {} with errors, {} duplicate group(s).",
+            self.files.len(),
+            self.failed_files(),
+            self.duplicates.len()
+        )
+    }
+}
+
+/// Validate all JSON files in a directory and its subdirectories.
+pub fn validate_directory(dir: &Path) -> Result<BatchReport, String> {
+    let mut paths = Vec::new();
+    collect_json_files(dir, &mut paths)?;
+    paths.sort();
+
+    let mut files = Vec::new();
+    for path in paths {
+        let name = path
+            .strip_prefix(dir)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        let content = fs::read_to_string(&path).map_err(|e| format!("cannot read file: {}", e));
+        files.push((name, content));
+    }
+    Ok(validate_files(files))
+}
+
+fn collect_json_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Cannot read directory {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Cannot read directory {}: {}", dir.display(), e))?
+            .path();
+        if path.is_dir() {
+            collect_json_files(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Validate a list of files, given as name and content (or the error reading it).
+pub fn validate_files(files: Vec<(String, Result<String, String>)>) -> BatchReport {
+    let mut report = BatchReport::default();
+    let mut schemes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (path, content) in files {
+        let mut file_report = FileReport {
+            path: path.clone(),
+            ..Default::default()
+        };
+
+        match content.and_then(|text| load_submission(&text)) {
+            Ok((app_entries, load_report)) => {
+                file_report.element = Some(format!("{:?}", app_entries.document.scheme_element));
+                if !load_report.migrations.is_empty() {
+                    file_report.warnings.push(format!(
+                        "File has format version {}, please normalize it.",
+                        load_report.format_version
+                    ));
+                }
+                file_report.errors.extend(load_report.schema_errors);
+                file_report
+                    .warnings
+                    .extend(submission_warnings(&app_entries));
+                match create_json_output(&app_entries) {
+                    Ok(_) if !file_report.errors.is_empty() => (),
+                    Ok(_) => {
+                        *report
+                            .coverage
+                            .entry(format!("{:?}", app_entries.document.scheme_element))
+                            .or_default() += 1;
+                        schemes
+                            .entry(scheme_key(&app_entries))
+                            .or_default()
+                            .push(path);
+                    }
+                    Err(e) => file_report.errors.push(e),
+                }
+            }
+            Err(e) => file_report.errors.push(e),
+        }
+
+        report.files.push(file_report);
+    }
+
+    report.duplicates = schemes.into_values().filter(|v| v.len() > 1).collect();
+    report
+}
+
+/// Checks that do not make a submission invalid but should be looked at.
+fn submission_warnings(app_entries: &TemplateApp) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if app_entries.document.references.is_empty() {
+        warnings.push("No references given.".to_string());
+    }
+    if app_entries.document.scheme_gs.term_symbol.is_empty() {
+        warnings.push("Ground state term symbol is missing.".to_string());
+    }
+    for (it, trans) in app_entries.document.scheme_transitions.iter().enumerate() {
+        if !trans.level.is_empty() && trans.term_symbol.is_empty() {
+            warnings.push(format!("Step {} has no term symbol.", it + 1));
+        }
+    }
+    warnings
+}
+
+/// Key that identifies a scheme: element, unit, and the numerical values of all levels.
+fn scheme_key(app_entries: &TemplateApp) -> String {
+    let levels: Vec<String> = app_entries
+        .document
+        .scheme_transitions
+        .iter()
+        .filter(|t| !t.level.is_empty())
+        .map(|t| match t.level.parse::<f64>() {
+            Ok(level) => format!("{}{}", level, if t.low_lying { "L" } else { "" }),
+            Err(_) => t.level.clone(),
+        })
+        .collect();
+    format!(
+        "{:?} {} {}",
+        app_entries.document.scheme_element,
+        app_entries.document.scheme_unit,
+        levels.join(" ")
+    )
+}
+
+fn xml_escape(inp: &str) -> String {
+    inp.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+fn test_entry(element: &str, level: &str, submitted_by: &str) -> Result<String, String> {
+    Ok(format!(
+        r#"{{"format_version": 1, "rims_scheme": {{"scheme": {{"element": "{}", "gs_level": "0", "gs_term": "3F2",
+        "ip_term": "", "lasers": "Ti:Sa", "last_step_to_ip": false, "step_level0": "{}",
+        "step_term0": "3G3", "unit": "nm"}}}},
+        "references": [{{"id": "10.500/abc", "author": "", "year": 0}}],
+        "notes": "", "saturation_curves": [], "submitted_by": "{}"}}"#,
+        element, level, submitted_by
+    ))
+}
+
+#[cfg(test)]
+#[test]
+fn test_validate_files() {
+    let files = vec![
+        ("ti/a.json".to_string(), test_entry("Ti", "393.8", "Jane")),
+        ("ti/b.json".to_string(), test_entry("Ti", "393.80", "John")),
+        ("fe/a.json".to_string(), test_entry("Fe", "302.1", "")),
+        ("zr/a.json".to_string(), test_entry("Zr", "300.0", "Jane")),
+        ("bad.json".to_string(), Ok("{".to_string())),
+    ];
+    let report = validate_files(files);
+
+    assert_eq!(report.failed_files(), 2);
+    assert_eq!(
+        report.files[2].errors,
+        vec![
+            "/submitted_by: String must have at least 1 character(s).",
+            "Please enter your name."
+        ]
+    );
+    assert_eq!(report.coverage.get("Ti"), Some(&2));
+    assert_eq!(report.coverage.get("Fe"), None);
+    assert_eq!(
+        report.duplicates,
+        vec![vec!["ti/a.json".to_string(), "ti/b.json".to_string()]]
+    );
+    assert!(report.has_errors());
+    assert!(report.files[0].warnings.is_empty());
+}
+
+#[test]
+fn test_report_formats() {
+    let report = validate_files(vec![
+        ("a&b.json".to_string(), Ok("{}".to_string())),
+        ("c.json".to_string(), test_entry("Ti", "393.8", "Jane")),
+    ]);
+
+    let text = report.render(ReportFormat::Text);
+    assert!(text.contains("ERROR a&b.json"));
+    assert!(text.contains("Element coverage: 1 of"));
+
+    let json: serde_json::Value = serde_json::from_str(&report.render(ReportFormat::Json)).unwrap();
+    assert_eq!(json["files"][1]["element"], "Ti");
+
+    let junit = report.render(ReportFormat::Junit);
+    assert!(junit.contains("tests=\"3\" failures=\"1\""));
+    assert!(junit.contains("name=\"a&amp;b.json\""));
+}
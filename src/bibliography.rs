@@ -0,0 +1,441 @@
+//! Import of references from BibTeX and RIS files, e.g., as exported from Zotero.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::{extract_doi, ReferenceEntry, ReferenceId, ReferenceKind};
+
+/// A single bibliography record as read from a BibTeX or RIS file.
+#[derive(Debug, Default, PartialEq)]
+struct BibRecord {
+    doi: Option<String>,
+    url: Option<String>,
+    authors: Vec<String>,
+    year: Option<usize>,
+    title: Option<String>,
+    journal: Option<String>,
+    volume: Option<String>,
+    start_page: Option<String>,
+    end_page: Option<String>,
+    kind: ReferenceKind,
+}
+
+impl BibRecord {
+    /// Turn the record into a reference entry, preferring the DOI and otherwise the URL with the
+    /// first author's surname and the year. Returns `None` if neither is possible.
+    fn to_reference_entry(&self) -> Option<ReferenceEntry> {
+        let entry = self.to_plain_reference_entry()?;
+        let pages = match (&self.start_page, &self.end_page) {
+            (Some(sp), Some(ep)) => format!("{}-{}", sp, ep),
+            (Some(sp), None) => sp.clone(),
+            _ => String::new(),
+        };
+        Some(entry.with_details(
+            self.title.as_deref().unwrap_or(""),
+            self.journal.as_deref().unwrap_or(""),
+            self.volume.as_deref().unwrap_or(""),
+            &pages,
+            self.kind.clone(),
+        ))
+    }
+
+    fn to_plain_reference_entry(&self) -> Option<ReferenceEntry> {
+        if let Some(doi) = self.doi.as_deref().and_then(extract_doi) {
+            return Some(ReferenceEntry::new_from_doi(&doi));
+        }
+
+        let url = match self.url.as_ref()?.parse::<ReferenceId>().ok()? {
+            ReferenceId::Doi(doi) => return Some(ReferenceEntry::new_from_doi(&doi)),
+            ReferenceId::Url(url) => url,
+        };
+        let year = self.year?;
+        let first_author = self.authors.first()?;
+        let mut author = author_surname(first_author);
+        if author.is_empty() {
+            return None;
+        }
+        if self.authors.len() > 1 {
+            author.push_str(" et al.");
+        }
+        Some(ReferenceEntry::new_from_url(&url, &author, year))
+    }
+}
+
+/// Result of importing a bibliography file.
+#[derive(Debug, Default)]
+pub struct BibImport {
+    pub entries: Vec<ReferenceEntry>,
+    /// Number of records that had neither a DOI nor a URL with author and year.
+    pub unusable: usize,
+}
+
+/// Parse the content of a `.bib` or `.ris` file into reference entries.
+///
+/// The format is detected from the content: RIS files start their records with a `TY  -` tag,
+/// everything else is read as BibTeX.
+pub fn parse_bibliography(text: &str) -> Result<BibImport, String> {
+    let records = if text.lines().any(|l| l.trim_start().starts_with("TY  -")) {
+        parse_ris(text)
+    } else {
+        parse_bibtex(text)?
+    };
+
+    if records.is_empty() {
+        return Err("No references found in the file.".to_string());
+    }
+
+    let mut import = BibImport::default();
+    for record in records {
+        match record.to_reference_entry() {
+            Some(entry) => import.entries.push(entry),
+            None => import.unusable += 1,
+        }
+    }
+    Ok(import)
+}
+
+/// Add imported entries to the list of references, skipping the ones whose id already exists.
+/// Returns the number of entries that were added.
+pub fn merge_references(references: &mut Vec<ReferenceEntry>, new: Vec<ReferenceEntry>) -> usize {
+    let mut added = 0;
+    for entry in new {
+        if references.iter().any(|r| r.id == entry.id) {
+            continue;
+        }
+        references.push(entry);
+        added += 1;
+    }
+    added
+}
+
+/// Parse BibTeX entries. `@comment`, `@string` and `@preamble` blocks are ignored.
+fn parse_bibtex(text: &str) -> Result<Vec<BibRecord>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut records = Vec::new();
+    let mut pos = 0;
+
+    while let Some(at) = chars[pos..].iter().position(|c| *c == '@') {
+        pos += at + 1;
+
+        let start_type = pos;
+        while pos < chars.len() && chars[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        let entry_type: String = chars[start_type..pos].iter().collect::<String>();
+        skip_whitespace(&chars, &mut pos);
+
+        let close = match chars.get(pos) {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => continue,
+        };
+        pos += 1;
+
+        let body_start = pos;
+        let body_end = find_closing(&chars, body_start, close).ok_or(format!(
+            "Unbalanced braces in BibTeX entry of type '{}'.",
+            entry_type
+        ))?;
+        pos = body_end + 1;
+
+        match entry_type.to_lowercase().as_str() {
+            "comment" | "string" | "preamble" => continue,
+            _ => (),
+        }
+
+        let body: String = chars[body_start..body_end].iter().collect();
+        // The citation key is everything up to the first comma.
+        let fields = match body.find(',') {
+            Some(idx) => &body[idx + 1..],
+            None => continue,
+        };
+
+        let mut record = BibRecord {
+            kind: bibtex_kind(&entry_type),
+            ..Default::default()
+        };
+        for (name, value) in parse_bibtex_fields(fields) {
+            match name.as_str() {
+                "doi" => record.doi = Some(value),
+                "url" => record.url = Some(value),
+                "author" => {
+                    record.authors = value
+                        .split(" and ")
+                        .map(|a| a.trim().to_string())
+                        .filter(|a| !a.is_empty())
+                        .collect()
+                }
+                "year" => record.year = parse_year(&value),
+                "date" if record.year.is_none() => record.year = parse_year(&value),
+                "title" => record.title = Some(value),
+                "journal" | "booktitle" | "school" | "institution" => record.journal = Some(value),
+                "publisher" if record.journal.is_none() => record.journal = Some(value),
+                "volume" => record.volume = Some(value),
+                "pages" => record.start_page = Some(value.replace("--", "-")),
+                _ => (),
+            }
+        }
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Parse the `name = value` pairs of a BibTeX entry body (without citation key).
+/// Values can be enclosed in braces, quotes, or be bare words / numbers.
+fn parse_bibtex_fields(fields: &str) -> Vec<(String, String)> {
+    let chars: Vec<char> = fields.chars().collect();
+    let mut ret = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        skip_whitespace(&chars, &mut pos);
+        while pos < chars.len() && chars[pos] == ',' {
+            pos += 1;
+            skip_whitespace(&chars, &mut pos);
+        }
+        if pos >= chars.len() {
+            break;
+        }
+
+        let name_start = pos;
+        while pos < chars.len() && chars[pos] != '=' && chars[pos] != ',' {
+            pos += 1;
+        }
+        let name: String = chars[name_start..pos].iter().collect();
+        let name = name.trim().to_lowercase();
+        if pos >= chars.len() || chars[pos] != '=' {
+            continue;
+        }
+        pos += 1;
+        skip_whitespace(&chars, &mut pos);
+
+        let value: String = match chars.get(pos) {
+            Some('{') => {
+                let end = find_closing(&chars, pos + 1, '}').unwrap_or(chars.len());
+                let val = chars[pos + 1..end].iter().collect();
+                pos = end + 1;
+                val
+            }
+            Some('"') => {
+                let start = pos + 1;
+                let mut end = start;
+                let mut depth = 0;
+                while end < chars.len() && !(chars[end] == '"' && depth == 0) {
+                    match chars[end] {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => (),
+                    }
+                    end += 1;
+                }
+                let val = chars[start..end.min(chars.len())].iter().collect();
+                pos = end + 1;
+                val
+            }
+            _ => {
+                let start = pos;
+                while pos < chars.len() && chars[pos] != ',' {
+                    pos += 1;
+                }
+                chars[start..pos].iter().collect()
+            }
+        };
+
+        ret.push((name, clean_bibtex_value(&value)));
+    }
+
+    ret
+}
+
+/// Remove grouping braces and collapse whitespace in a BibTeX value.
+fn clean_bibtex_value(value: &str) -> String {
+    value
+        .replace(['{', '}'], "")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Find the index of the closing character that matches an already opened brace or parenthesis.
+fn find_closing(chars: &[char], start: usize, close: char) -> Option<usize> {
+    let open = match close {
+        '}' => '{',
+        ')' => '(',
+        _ => return None,
+    };
+    let mut depth = 0;
+    for (it, c) in chars.iter().enumerate().skip(start) {
+        if *c == open {
+            depth += 1;
+        } else if *c == close {
+            if depth == 0 {
+                return Some(it);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+fn skip_whitespace(chars: &[char], pos: &mut usize) {
+    while *pos < chars.len() && chars[*pos].is_whitespace() {
+        *pos += 1;
+    }
+}
+
+/// Parse RIS records, which are delimited by the `TY` and `ER` tags.
+fn parse_ris(text: &str) -> Vec<BibRecord> {
+    let mut records = Vec::new();
+    let mut current: Option<BibRecord> = None;
+
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.get(2..5) != Some("  -") {
+            continue;
+        }
+        let tag = &line[..2];
+        let value = line[5..].trim().to_string();
+
+        match tag {
+            "TY" => {
+                current = Some(BibRecord {
+                    kind: ris_kind(&value),
+                    ..Default::default()
+                })
+            }
+            "ER" => {
+                if let Some(record) = current.take() {
+                    records.push(record);
+                }
+            }
+            _ => {
+                let record = match current.as_mut() {
+                    Some(r) => r,
+                    None => continue,
+                };
+                match tag {
+                    "DO" => record.doi = Some(value),
+                    "UR" if record.url.is_none() => record.url = Some(value),
+                    "AU" | "A1" => record.authors.push(value),
+                    "TI" | "T1" if record.title.is_none() => record.title = Some(value),
+                    "JO" | "JF" | "T2" | "JA" if record.journal.is_none() => {
+                        record.journal = Some(value)
+                    }
+                    "VL" => record.volume = Some(value),
+                    "SP" => record.start_page = Some(value),
+                    "EP" => record.end_page = Some(value),
+                    "PY" | "Y1" | "DA" if record.year.is_none() => record.year = parse_year(&value),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    // A file that is missing the last end-of-record tag
+    if let Some(record) = current {
+        records.push(record);
+    }
+
+    records
+}
+
+/// Map a BibTeX entry type to the kind of reference.
+fn bibtex_kind(entry_type: &str) -> ReferenceKind {
+    match entry_type.to_lowercase().as_str() {
+        "phdthesis" | "mastersthesis" | "thesis" => ReferenceKind::Thesis,
+        "inproceedings" | "proceedings" | "conference" => ReferenceKind::Proceedings,
+        "dataset" | "data" => ReferenceKind::Dataset,
+        _ => ReferenceKind::Article,
+    }
+}
+
+/// Map a RIS `TY` tag to the kind of reference.
+fn ris_kind(ty: &str) -> ReferenceKind {
+    match ty {
+        "THES" => ReferenceKind::Thesis,
+        "CONF" | "CPAPER" => ReferenceKind::Proceedings,
+        "DATA" => ReferenceKind::Dataset,
+        _ => ReferenceKind::Article,
+    }
+}
+
+/// Get the first four-digit number from a date string.
+fn parse_year(value: &str) -> Option<usize> {
+    static RE_YEAR: OnceLock<Regex> = OnceLock::new();
+    let re = RE_YEAR.get_or_init(|| Regex::new(r"\d{4}").unwrap());
+    re.find(value)
+        .and_then(|m| m.as_str().parse::<usize>().ok())
+}
+
+/// Get the surname from an author in "Last, First" or "First Last" notation.
+fn author_surname(author: &str) -> String {
+    match author.split_once(',') {
+        Some((last, _)) => last.trim().to_string(),
+        None => author.split_whitespace().last().unwrap_or("").to_string(),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_bibtex() {
+    let bib = r#"
+@comment{This is ignored}
+@article{Wendt1999,
+  author = {Wendt, K. and Trautmann, N.},
+  title = {Laser resonance ionization},
+  year = 1999,
+  doi = {https://doi.org/10.1016/S0168-1176(99)00112-7},
+}
+@misc{rims,
+  author = "Rothe, Sebastian",
+  url = {https://example.com/rims},
+  year = {2013}
+}
+@misc{nourl, author = {Someone}, year = {2000}}
+"#;
+    let import = parse_bibliography(bib).unwrap();
+    assert_eq!(import.entries.len(), 2);
+    assert_eq!(import.unusable, 1);
+    assert_eq!(import.entries[0].id, "10.1016/s0168-1176(99)00112-7");
+    assert_eq!(
+        import.entries[0].title.as_deref(),
+        Some("Laser resonance ionization")
+    );
+    assert!(import.entries[0].author.is_empty());
+    assert_eq!(import.entries[1].id, "https://example.com/rims");
+    assert_eq!(import.entries[1].author, "Rothe");
+    assert_eq!(import.entries[1].year, 2013);
+    assert_eq!(import.entries[1].kind, ReferenceKind::Article);
+}
+
+#[test]
+fn test_parse_ris() {
+    let ris = "TY  - THES\r\n\
+AU  - Savina, Michael\r\n\
+AU  - Trappitsch, Reto\r\n\
+PY  - 2021/05/01/\r\n\
+UR  - https://example.com/savina\r\n\
+ER  - \r\n\
+TY  - JOUR\r\n\
+DO  - 10.500/123456789\r\n\
+ER  - \r\n";
+    let import = parse_bibliography(ris).unwrap();
+    assert_eq!(import.entries.len(), 2);
+    assert_eq!(import.entries[0].author, "Savina et al.");
+    assert_eq!(import.entries[0].year, 2021);
+    assert_eq!(import.entries[0].kind, ReferenceKind::Thesis);
+    assert_eq!(import.entries[1].id, "10.500/123456789");
+}
+
+#[test]
+fn test_merge_references_skips_duplicates() {
+    let mut refs = vec![ReferenceEntry::new_from_doi("10.500/123456789")];
+    let new = vec![
+        ReferenceEntry::new_from_doi("10.500/123456789"),
+        ReferenceEntry::new_from_doi("10.500/987654321"),
+    ];
+    assert_eq!(merge_references(&mut refs, new), 1);
+    assert_eq!(refs.len(), 2);
+}
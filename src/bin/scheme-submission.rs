@@ -0,0 +1,12 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+// Headless command line interface, see `scheme-submission --help`.
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(scheme_submission::cli::run(&args));
+}
+
+// The command line interface is not available on the web.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
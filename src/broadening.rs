@@ -0,0 +1,75 @@
+//! Doppler broadening of the transitions and the nuclear spin of the selected isotope, which are
+//! shown while planning a scheme.
+
+use crate::Elements;
+
+/// Boltzmann constant in J/K.
+const BOLTZMANN: f64 = 1.380_649e-23;
+/// Atomic mass unit in kg.
+const ATOMIC_MASS_UNIT: f64 = 1.660_539_066_60e-27;
+/// Speed of light in m/s.
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+
+/// Doppler FWHM in GHz of a transition at the vacuum `wavelength` in nm, for atoms with `mass` in
+/// u at `temperature` in K.
+pub fn doppler_fwhm(wavelength: f64, mass: f64, temperature: f64) -> f64 {
+    let frequency = SPEED_OF_LIGHT / (wavelength * 1e-9);
+    let relative = (8.0 * BOLTZMANN * temperature * 2f64.ln()
+        / (mass * ATOMIC_MASS_UNIT * SPEED_OF_LIGHT.powi(2)))
+    .sqrt();
+    frequency * relative * 1e-9
+}
+
+/// Mass number of an isotope of the element, `None` if no isotope is given.
+pub fn mass_number(element: &Elements, isotope: &str) -> Result<Option<u32>, String> {
+    match isotope.trim() {
+        "" => Ok(None),
+        a => match a.parse::<u32>() {
+            Ok(a) if a >= element.atomic_number() => Ok(Some(a)),
+            _ => Err(format!(
+                "{} is not a valid mass number of {:?}.",
+                a, element
+            )),
+        },
+    }
+}
+
+/// Mass in u of the isotope, approximated by its mass number, or the standard atomic weight of
+/// the element if no isotope is given.
+pub fn isotope_mass(element: &Elements, isotope: &str) -> Result<f64, String> {
+    Ok(match mass_number(element, isotope)? {
+        Some(a) => a as f64,
+        None => element.atomic_weight(),
+    })
+}
+
+/// Nuclear spin of the isotope: 0 for even-even nuclei, otherwise the entered value if any.
+pub fn nuclear_spin(element: &Elements, isotope: &str, entered: &str) -> Option<String> {
+    let z = element.atomic_number();
+    match mass_number(element, isotope) {
+        Ok(Some(a)) if z % 2 == 0 && (a - z) % 2 == 0 => Some("0".to_string()),
+        Ok(Some(_)) if !entered.trim().is_empty() => Some(entered.trim().to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_doppler_fwhm() {
+    // Ti at 394 nm and 2000 K: about 3.5 GHz
+    let fwhm = doppler_fwhm(394.0, Elements::Ti.atomic_weight(), 2000.0);
+    assert!((fwhm - 3.50).abs() < 0.05, "{}", fwhm);
+    // scales with sqrt(T / m)
+    let hot = doppler_fwhm(394.0, Elements::Ti.atomic_weight(), 8000.0);
+    assert!((hot / fwhm - 2.0).abs() < 1e-12);
+
+    assert_eq!(Elements::Ti.atomic_number(), 22);
+    assert_eq!(isotope_mass(&Elements::Ti, "48"), Ok(48.0));
+    assert!(isotope_mass(&Elements::Ti, "12").is_err());
+    assert_eq!(nuclear_spin(&Elements::Ti, "48", ""), Some("0".to_string()));
+    assert_eq!(nuclear_spin(&Elements::Ti, "47", ""), None);
+    assert_eq!(
+        nuclear_spin(&Elements::Ti, "47", "5/2"),
+        Some("5/2".to_string())
+    );
+}
@@ -0,0 +1,380 @@
+//! Command line interface to validate and convert submissions without the GUI.
+//!
+//! The binary `scheme-submission` only forwards its arguments to [`run`], which returns the
+//! process exit code.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::batch::{validate_directory, ReportFormat};
+use crate::schema::submission_schema;
+use crate::{
+    create_drawer_output, create_json_output, load_submission, LoadReport, SubmissionFormat,
+    TemplateApp,
+};
+
+/// Everything went fine.
+pub const EXIT_OK: i32 = 0;
+/// At least one file failed validation or conversion.
+pub const EXIT_INVALID: i32 = 1;
+/// The command line could not be parsed or a file could not be read / written, by any command.
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "Usage: scheme-submission <command> [options]
+
+Commands:
+  validate <file>...         Check submission files with the same checks as the app.
+  normalize <file>           Re-emit a submission as canonical database JSON.
+  convert <file>             Convert between RIMSSchemeDrawer and database format.
+  batch <directory>          Validate all JSON files in a directory tree and report
+                             errors, warnings, element coverage, and duplicates.
+  schema                     Print the JSON Schema of the database format.
+
+Options:
+  -o, --output <file>        Write the result to a file instead of stdout.
+  --to <database|drawer>     Target format for convert (default: the other format).
+  --submitted-by <name>      Name of the submitter, used if the file does not contain one.
+  --format <text|json|junit> Report format for batch (default: text).
+  -h, --help                 Show this help.";
+
+/// Parsed command line options.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    files: Vec<String>,
+    output: Option<String>,
+    to: Option<SubmissionFormat>,
+    submitted_by: Option<String>,
+    format: Option<ReportFormat>,
+    help: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut it = args.iter();
+        while let Some(arg) = it.next() {
+            match arg.as_str() {
+                "-o" | "--output" => options.output = Some(option_value(&mut it, arg)?),
+                "--to" => options.to = Some(option_value(&mut it, arg)?.parse()?),
+                "--submitted-by" => options.submitted_by = Some(option_value(&mut it, arg)?),
+                "--format" => options.format = Some(option_value(&mut it, arg)?.parse()?),
+                "-h" | "--help" => options.help = true,
+                a if a.starts_with('-') => return Err(format!("Unknown option '{}'.", a)),
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Get the single input file of a command.
+    fn single_file(&self, command: &str) -> Result<&str, String> {
+        match self.files.as_slice() {
+            [file] => Ok(file),
+            _ => Err(format!("'{}' expects exactly one file.", command)),
+        }
+    }
+}
+
+fn option_value<'a>(
+    it: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<String, String> {
+    match it.next() {
+        Some(value) => Ok(value.clone()),
+        None => Err(format!("Option '{}' requires a value.", option)),
+    }
+}
+
+/// Run the command line interface with the given arguments (without the program name).
+/// Returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => {
+            eprintln!("{}", USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    if command == "-h" || command == "--help" {
+        println!("{}", USAGE);
+        return EXIT_OK;
+    }
+
+    // option values are parsed first, such that, e.g., `--submitted-by -h` is not a help request
+    let options = match Options::parse(rest) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return EXIT_OK;
+    }
+
+    match command {
+        "validate" => validate(&options),
+        "batch" => batch(&options),
+        "schema" => match serde_json::to_string_pretty(&submission_schema()) {
+            Ok(out) => write_output(&out, &options),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                EXIT_INVALID
+            }
+        },
+        "normalize" | "convert" => {
+            let file = match options.single_file(command) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return EXIT_USAGE;
+                }
+            };
+            let text = match fs::read_to_string(file) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("Error: cannot read {}: {}", file, e);
+                    return EXIT_USAGE;
+                }
+            };
+            let result = match command {
+                "normalize" => normalize_text(&text, &options),
+                _ => convert_text(&text, &options),
+            };
+            match result {
+                Ok(out) => write_output(&out, &options),
+                Err(e) => {
+                    eprintln!("{}: {}", file, e);
+                    EXIT_INVALID
+                }
+            }
+        }
+        _ => {
+            eprintln!("Error: Unknown command '{}'.\n\n{}", command, USAGE);
+            EXIT_USAGE
+        }
+    }
+}
+
+/// Validate all given files and report each of them.
+fn validate(options: &Options) -> i32 {
+    if options.files.is_empty() {
+        eprintln!("Error: 'validate' expects at least one file.");
+        return EXIT_USAGE;
+    }
+
+    // files that cannot be read take precedence over invalid ones in the exit code
+    let mut exit_code = EXIT_OK;
+    for file in options.files.iter() {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("ERROR {}: cannot read file: {}", file, e);
+                exit_code = EXIT_USAGE;
+                continue;
+            }
+        };
+        match validate_text(&text, options) {
+            Ok(()) => println!("OK    {}", file),
+            Err(e) => {
+                println!("ERROR {}: {}", file, e);
+                exit_code = exit_code.max(EXIT_INVALID);
+            }
+        }
+    }
+    exit_code
+}
+
+/// Validate a whole directory tree and write the report.
+fn batch(options: &Options) -> i32 {
+    let dir = match options.single_file("batch") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return EXIT_USAGE;
+        }
+    };
+    let report = match validate_directory(Path::new(dir)) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return EXIT_USAGE;
+        }
+    };
+
+    let out = report.render(options.format.unwrap_or(ReportFormat::Text));
+    match write_output(&out, options) {
+        EXIT_OK if report.has_errors() => EXIT_INVALID,
+        code => code,
+    }
+}
+
+fn write_output(out: &str, options: &Options) -> i32 {
+    match &options.output {
+        Some(path) => match fs::write(path, format!("{}\n", out)) {
+            Ok(()) => EXIT_OK,
+            Err(e) => {
+                eprintln!("Error: cannot write {}: {}", path, e);
+                EXIT_USAGE
+            }
+        },
+        None => {
+            println!("{}", out);
+            EXIT_OK
+        }
+    }
+}
+
+/// Load a file into a fresh app state, filling in the submitter from the options if needed.
+fn load(text: &str, options: &Options) -> Result<(TemplateApp, LoadReport), String> {
+    let (mut app_entries, report) = load_submission(text)?;
+    for migration in report.migrations.iter() {
+        eprintln!("Migration applied: {}", migration);
+    }
+    for field in report.fields.iter() {
+        eprintln!("Import: {}", field);
+    }
+    if app_entries.submitted_by.is_empty() {
+        if let Some(name) = &options.submitted_by {
+            app_entries.submitted_by.clone_from(name);
+        }
+    }
+    Ok((app_entries, report))
+}
+
+/// Run the same checks as the app does before submitting. Database entries must furthermore
+/// match the submission schema.
+fn validate_text(text: &str, options: &Options) -> Result<(), String> {
+    let (app_entries, report) = load(text, options)?;
+    if !report.schema_errors.is_empty() {
+        return Err(format!(
+            "File does not match the submission schema:\n  {}",
+            report.schema_errors.join("\n  ")
+        ));
+    }
+    create_json_output(&app_entries)?;
+    Ok(())
+}
+
+/// Round-trip a file through load and export to get canonical database JSON.
+fn normalize_text(text: &str, options: &Options) -> Result<String, String> {
+    create_json_output(&load(text, options)?.0)
+}
+
+/// Convert a file into the requested format, by default into the respective other one.
+fn convert_text(text: &str, options: &Options) -> Result<String, String> {
+    let json: Value = match serde_json::from_str(text) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Error reading JSON file: {}", e)),
+    };
+    let target = match (options.to, SubmissionFormat::detect(&json)) {
+        (Some(to), _) => to,
+        (None, Some(SubmissionFormat::Database)) => SubmissionFormat::Drawer,
+        (None, Some(SubmissionFormat::Drawer)) => SubmissionFormat::Database,
+        (None, None) => {
+            return Err("No 'rims_scheme' or 'scheme' key found in the JSON file.".to_string())
+        }
+    };
+
+    let (app_entries, _) = load(text, options)?;
+    match target {
+        SubmissionFormat::Database => create_json_output(&app_entries),
+        SubmissionFormat::Drawer => create_drawer_output(&app_entries),
+    }
+}
+
+#[cfg(test)]
+const DRAWER_TEST_FILE: &str = r#"{
+    "scheme": {
+        "element": "Ti",
+        "gs_level": "0",
+        "gs_term": "3F2",
+        "ip_term": "",
+        "lasers": "Ti:Sa",
+        "last_step_to_ip": false,
+        "step_level0": "25388.334",
+        "step_term0": "3G3",
+        "trans_strength0": "",
+        "step_level1": "50145.6",
+        "step_term1": "",
+        "trans_strength1": "",
+        "unit": "cm<sup>-1</sup>"
+    }
+}"#;
+
+#[cfg(test)]
+#[test]
+fn test_parse_options() {
+    let args: Vec<String> = ["a.json", "--to", "drawer", "-o", "out.json"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = Options::parse(&args).unwrap();
+    assert_eq!(options.files, vec!["a.json".to_string()]);
+    assert_eq!(options.to, Some(SubmissionFormat::Drawer));
+    assert_eq!(options.output.as_deref(), Some("out.json"));
+
+    assert!(Options::parse(&["--to".to_string()]).is_err());
+    assert!(Options::parse(&["--bogus".to_string()]).is_err());
+}
+
+#[test]
+fn test_validate_text() {
+    let mut options = Options::default();
+    assert_eq!(
+        validate_text(DRAWER_TEST_FILE, &options),
+        Err("Please enter your name.".to_string())
+    );
+    options.submitted_by = Some("Jane Doe".to_string());
+    assert!(validate_text(DRAWER_TEST_FILE, &options).is_ok());
+    assert!(validate_text("{}", &options).is_err());
+
+    let database = convert_text(DRAWER_TEST_FILE, &options).unwrap();
+    assert!(validate_text(&database, &options).is_ok());
+    let broken = database.replace("\"step_level0\": \"25388.334\"", "\"step_level0\": 1");
+    let err = validate_text(&broken, &options).unwrap_err();
+    assert!(err.contains("/rims_scheme/scheme/step_level0: Expected string, found number."));
+}
+
+#[test]
+fn test_convert_round_trip() {
+    let options = Options {
+        submitted_by: Some("Jane Doe".to_string()),
+        ..Default::default()
+    };
+    let database = convert_text(DRAWER_TEST_FILE, &options).unwrap();
+    assert!(database.contains("\"rims_scheme\""));
+    assert_eq!(normalize_text(&database, &options).unwrap(), database);
+
+    let drawer = convert_text(&database, &options).unwrap();
+    let drawer_json: Value = serde_json::from_str(&drawer).unwrap();
+    assert_eq!(drawer_json["scheme"]["step_level1"], "50145.6");
+    assert!(drawer_json.get("rims_scheme").is_none());
+}
+
+#[test]
+fn test_run_exit_codes() {
+    assert_eq!(run(&[]), EXIT_USAGE);
+    assert_eq!(run(&["--help".to_string()]), EXIT_OK);
+    assert_eq!(run(&["frobnicate".to_string()]), EXIT_USAGE);
+    assert_eq!(run(&["normalize".to_string()]), EXIT_USAGE);
+    assert_eq!(
+        run(&["batch".to_string(), "/nonexistent/directory".to_string()]),
+        EXIT_USAGE
+    );
+    assert_eq!(
+        run(&["validate".to_string(), "/nonexistent/file.json".to_string()]),
+        EXIT_USAGE
+    );
+    // -h as the value of an option is not a help request
+    let args: Vec<String> = ["normalize", "--submitted-by", "-h"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect();
+    assert_eq!(run(&args), EXIT_USAGE);
+    assert_eq!(run(&["schema".to_string(), "-h".to_string()]), EXIT_OK);
+}
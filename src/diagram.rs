@@ -0,0 +1,673 @@
+//! Energy level diagram of the scheme.
+//!
+//! The layout is computed once into simple drawing primitives (lines, arrows, texts) in a
+//! coordinate system with the origin at the top left, such that every backend only has to
+//! translate the primitives.
+
+use crate::{strip_latex_dollars, TemplateApp, TransitionUnit, STEPS};
+
+/// RGB color of a primitive.
+pub type Color = [u8; 3];
+
+/// Color that is either fixed or the foreground color of the backend, e.g., white in dark mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ink {
+    Foreground,
+    Rgb(Color),
+}
+
+/// Horizontal alignment of a text with respect to its position. Texts are always centered
+/// vertically.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Primitive {
+    Line {
+        from: [f32; 2],
+        to: [f32; 2],
+        ink: Ink,
+        width: f32,
+        dashed: bool,
+    },
+    /// Vertical arrow for a transition, drawn from `from` to `to`.
+    Arrow {
+        from: [f32; 2],
+        to: [f32; 2],
+        color: Color,
+        hollow: bool,
+    },
+    Text {
+        pos: [f32; 2],
+        text: String,
+        size: f32,
+        anchor: Anchor,
+        ink: Ink,
+    },
+}
+
+/// Labels of a step that can be moved on the diagram.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelKind {
+    Wavelength,
+    Term,
+}
+
+/// Part of the diagram that belongs to a step, given by its index in the scheme transitions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// Level or arrow of a step.
+    Step(usize),
+    Label(LabelKind, usize),
+}
+
+/// Rectangle of the diagram that can be clicked or dragged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HitBox {
+    pub target: Target,
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+/// All primitives that make up the diagram.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchemeLayout {
+    pub width: f32,
+    pub height: f32,
+    pub primitives: Vec<Primitive>,
+    pub targets: Vec<HitBox>,
+}
+
+impl SchemeLayout {
+    /// Get the part of the diagram at the given position. Labels take precedence over levels.
+    pub fn target_at(&self, pos: [f32; 2]) -> Option<Target> {
+        let hits = self.targets.iter().filter(|h| {
+            (h.min[0]..=h.max[0]).contains(&pos[0]) && (h.min[1]..=h.max[1]).contains(&pos[1])
+        });
+        let mut step = None;
+        for hit in hits {
+            match hit.target {
+                Target::Label(..) => return Some(hit.target),
+                Target::Step(_) => step = step.or(Some(hit.target)),
+            }
+        }
+        step
+    }
+
+    /// Add a level with its term symbol. Levels of steps (`index` is given) can be clicked and
+    /// their labels moved.
+    fn add_level(
+        &mut self,
+        index: Option<usize>,
+        x: [f32; 2],
+        y: f32,
+        term_symbol: &str,
+        offset: [f32; 2],
+    ) {
+        self.primitives.push(Primitive::Line {
+            from: [x[0], y],
+            to: [x[1], y],
+            ink: Ink::Foreground,
+            width: 1.5,
+            dashed: false,
+        });
+        let term_symbol = strip_latex_dollars(term_symbol);
+        let pos = [x[1] + 4.0 + offset[0], y + offset[1]];
+        match index {
+            Some(index) => {
+                self.targets.push(HitBox {
+                    target: Target::Step(index),
+                    min: [x[0], y - LEVEL_HIT_MARGIN],
+                    max: [x[1], y + LEVEL_HIT_MARGIN],
+                });
+                if !term_symbol.is_empty() {
+                    self.add_label(
+                        Target::Label(LabelKind::Term, index),
+                        pos,
+                        term_symbol.to_string(),
+                    );
+                }
+            }
+            None if !term_symbol.is_empty() => self.primitives.push(Primitive::Text {
+                pos,
+                text: term_symbol.to_string(),
+                size: FONT_SIZE,
+                anchor: Anchor::Start,
+                ink: Ink::Foreground,
+            }),
+            None => (),
+        }
+    }
+
+    /// Add a label that can be dragged. Its width is estimated from the number of characters.
+    fn add_label(&mut self, target: Target, pos: [f32; 2], text: String) {
+        let width = 0.6 * FONT_SIZE * text.chars().count() as f32;
+        self.targets.push(HitBox {
+            target,
+            min: [pos[0], pos[1] - FONT_SIZE / 2.0],
+            max: [pos[0] + width, pos[1] + FONT_SIZE / 2.0],
+        });
+        self.primitives.push(Primitive::Text {
+            pos,
+            text,
+            size: FONT_SIZE,
+            anchor: Anchor::Start,
+            ink: Ink::Foreground,
+        });
+    }
+}
+
+/// Offsets of the labels of each step in pixels, set by dragging them on the diagram.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LabelOffsets {
+    pub wavelength: [[f32; 2]; STEPS],
+    pub term: [[f32; 2]; STEPS],
+}
+
+impl LabelOffsets {
+    pub fn get_mut(&mut self, kind: LabelKind, index: usize) -> &mut [f32; 2] {
+        match kind {
+            LabelKind::Wavelength => &mut self.wavelength[index],
+            LabelKind::Term => &mut self.term[index],
+        }
+    }
+
+    /// Insert zero offsets for a new step at `index`, shifting the following steps up.
+    pub fn insert(&mut self, index: usize) {
+        for offsets in [&mut self.wavelength, &mut self.term] {
+            offsets[index..].rotate_right(1);
+            offsets[index] = [0.0, 0.0];
+        }
+    }
+}
+
+const MARGIN_LEFT: f32 = 70.0;
+const MARGIN_RIGHT: f32 = 80.0;
+const MARGIN_TOP: f32 = 20.0;
+const MARGIN_BOTTOM: f32 = 20.0;
+const ARROW_WIDTH: f32 = 6.0;
+const ARROW_HEAD_WIDTH: f32 = 16.0;
+const ARROW_HEAD_LENGTH: f32 = 14.0;
+const FONT_SIZE: f32 = 12.0;
+const FONT_SIZE_AXIS: f32 = 11.0;
+/// Half height of the clickable area around a level.
+const LEVEL_HIT_MARGIN: f32 = 4.0;
+
+/// A step of the scheme with its energies in cm⁻¹.
+struct Step {
+    index: usize,
+    lower: f64,
+    upper: f64,
+    term_symbol: String,
+    forbidden: bool,
+}
+
+/// Compute the diagram of the scheme for a drawing area of the given size.
+///
+/// Levels in nm are converted to energies by adding the transition energy to the level below.
+/// An error is returned if a level cannot be read.
+pub fn layout_scheme(
+    app_entries: &TemplateApp,
+    width: f32,
+    height: f32,
+) -> Result<SchemeLayout, String> {
+    let gs = ground_state_energy(app_entries)?;
+    let ip = app_entries.document.scheme_element.ip();
+    let energies = level_energies(app_entries, app_entries.document.scheme_transitions.len())?;
+
+    let mut low_lying: Vec<(usize, f64, String)> = Vec::new();
+    let mut steps: Vec<Step> = Vec::new();
+    let mut current = gs;
+    for (it, trans) in app_entries.document.scheme_transitions.iter().enumerate() {
+        let energy = match energies[it] {
+            Some(energy) => energy,
+            None => continue,
+        };
+        match trans.low_lying {
+            true => low_lying.push((it, energy, trans.term_symbol.clone())),
+            false => steps.push(Step {
+                index: it,
+                lower: current,
+                upper: energy,
+                term_symbol: trans.term_symbol.clone(),
+                forbidden: trans.forbidden,
+            }),
+        }
+        current = energy;
+    }
+
+    let e_min = low_lying.iter().map(|l| l.1).fold(gs, f64::min).min(0.0);
+    let e_max = steps.iter().map(|s| s.upper).fold(ip, f64::max) * 1.08;
+    let plot_bottom = height - MARGIN_BOTTOM;
+    let plot_height = plot_bottom - MARGIN_TOP;
+    let y = |energy: f64| -> f32 {
+        plot_bottom - ((energy - e_min) / (e_max - e_min)) as f32 * plot_height
+    };
+
+    let x_left = MARGIN_LEFT;
+    let x_right = (width - MARGIN_RIGHT).max(x_left + 1.0);
+    let column = (x_right - x_left) / steps.len().max(1) as f32;
+    let x_step = |k: usize| x_left + (k as f32 + 0.5) * column;
+
+    let mut layout = SchemeLayout {
+        width,
+        height,
+        ..Default::default()
+    };
+    let offsets = &app_entries.document.diagram_offsets;
+
+    add_axis(&mut layout.primitives, e_min, e_max, &y);
+
+    // Ground state, low-lying states, and the IP
+    layout.add_level(
+        None,
+        [x_left, x_right],
+        y(gs),
+        &app_entries.document.scheme_gs.term_symbol,
+        [0.0, 0.0],
+    );
+    let low_lying_end = x_step(0) + 0.35 * column;
+    for (index, energy, term) in low_lying.iter() {
+        layout.add_level(
+            Some(*index),
+            [x_left, low_lying_end],
+            y(*energy),
+            term,
+            offsets.term[*index],
+        );
+    }
+    layout.primitives.push(Primitive::Line {
+        from: [x_left, y(ip)],
+        to: [x_right, y(ip)],
+        ink: Ink::Foreground,
+        width: 1.5,
+        dashed: true,
+    });
+    layout.primitives.push(Primitive::Text {
+        pos: [x_right + 4.0, y(ip)],
+        text: format!(
+            "IP {}",
+            strip_latex_dollars(&app_entries.document.scheme_ip_term_symbol)
+        )
+        .trim()
+        .to_string(),
+        size: FONT_SIZE,
+        anchor: Anchor::Start,
+        ink: Ink::Foreground,
+    });
+
+    // Excitation steps
+    let last = steps.len().saturating_sub(1);
+    for (k, step) in steps.iter().enumerate() {
+        let x = x_step(k);
+        let to_ip = app_entries.document.scheme_last_step_to_ip && k == last;
+        let top = match to_ip {
+            true => y(ip),
+            false => y(step.upper),
+        };
+        let wavelength = 1e7 / (step.upper - step.lower);
+
+        if !to_ip {
+            let x_end = match k < last {
+                true => x_step(k + 1) + 0.35 * column,
+                false => x + 0.35 * column,
+            };
+            layout.add_level(
+                Some(step.index),
+                [x - 0.35 * column, x_end],
+                top,
+                &step.term_symbol,
+                offsets.term[step.index],
+            );
+        }
+        layout.primitives.push(Primitive::Arrow {
+            from: [x, y(step.lower)],
+            to: [x, top],
+            color: wavelength_color(wavelength),
+            hollow: to_ip,
+        });
+        layout.targets.push(HitBox {
+            target: Target::Step(step.index),
+            min: [x - ARROW_HEAD_WIDTH / 2.0, top],
+            max: [x + ARROW_HEAD_WIDTH / 2.0, y(step.lower)],
+        });
+        let offset = offsets.wavelength[step.index];
+        layout.add_label(
+            Target::Label(LabelKind::Wavelength, step.index),
+            [
+                x + ARROW_HEAD_WIDTH / 2.0 + 2.0 + offset[0],
+                (y(step.lower) + top) / 2.0 + offset[1],
+            ],
+            format!("{:.2} nm", wavelength),
+        );
+        if step.forbidden {
+            let yc = y(step.lower) - (y(step.lower) - top) / 3.0;
+            let d = ARROW_HEAD_WIDTH / 2.0;
+            for sign in [-1.0, 1.0] {
+                layout.primitives.push(Primitive::Line {
+                    from: [x - d, yc - sign * d],
+                    to: [x + d, yc + sign * d],
+                    ink: Ink::Rgb([200, 0, 0]),
+                    width: 2.0,
+                    dashed: false,
+                });
+            }
+        }
+    }
+
+    Ok(layout)
+}
+
+/// Energy of the ground state in cm⁻¹.
+pub fn ground_state_energy(app_entries: &TemplateApp) -> Result<f64, String> {
+    Ok(parse_energy(&app_entries.document.scheme_gs.level, "Ground state")?.unwrap_or(0.0))
+}
+
+/// Energies in cm⁻¹ of the levels of the first `count` steps, `None` for empty steps.
+///
+/// Levels in nm are converted to energies by adding the transition energy to the level below,
+/// low-lying states are always given in cm⁻¹.
+pub fn level_energies(app_entries: &TemplateApp, count: usize) -> Result<Vec<Option<f64>>, String> {
+    let mut current = ground_state_energy(app_entries)?;
+    let mut energies = Vec::new();
+    for (it, trans) in app_entries
+        .document
+        .scheme_transitions
+        .iter()
+        .take(count)
+        .enumerate()
+    {
+        let level = match parse_energy(&trans.level, &format!("Step {}", it + 1))? {
+            Some(level) => level,
+            None => {
+                energies.push(None);
+                continue;
+            }
+        };
+        let energy = match (trans.low_lying, &app_entries.document.scheme_unit) {
+            (true, _) | (false, TransitionUnit::CM1) => level,
+            (false, TransitionUnit::NM) if level > 0.0 => current + 1e7 / level,
+            (false, TransitionUnit::NM) => {
+                return Err(format!("Step {}: Invalid wavelength.", it + 1))
+            }
+        };
+        if !trans.low_lying && energy <= current {
+            return Err(format!(
+                "Step {}: Level is not above the previous level.",
+                it + 1
+            ));
+        }
+        energies.push(Some(energy));
+        current = energy;
+    }
+    Ok(energies)
+}
+
+/// Energy in cm⁻¹ of the level that the step at `index` starts from, i.e., the closest filled
+/// level below it or the ground state. `None` if one of these levels cannot be read.
+pub fn lower_energy(app_entries: &TemplateApp, index: usize) -> Option<f64> {
+    let energies = level_energies(app_entries, index).ok()?;
+    match energies.into_iter().flatten().last() {
+        Some(energy) => Some(energy),
+        None => ground_state_energy(app_entries).ok(),
+    }
+}
+
+/// Outline of an arrow from `from` to `to`, which is used for hollow arrows and by backends that
+/// can fill non-convex polygons.
+pub fn arrow_outline(from: [f32; 2], to: [f32; 2]) -> Vec<[f32; 2]> {
+    let dir = if to[1] < from[1] { 1.0 } else { -1.0 };
+    let head = to[1] + dir * ARROW_HEAD_LENGTH.min((to[1] - from[1]).abs());
+    let (x, aw, hw) = (from[0], ARROW_WIDTH / 2.0, ARROW_HEAD_WIDTH / 2.0);
+    vec![
+        [x - aw, from[1]],
+        [x - aw, head],
+        [x - hw, head],
+        [x, to[1]],
+        [x + hw, head],
+        [x + aw, head],
+        [x + aw, from[1]],
+    ]
+}
+
+/// Color of a transition: the visible color of the wavelength, purple for UV, and dark red for
+/// IR light.
+pub fn wavelength_color(nm: f64) -> Color {
+    let (r, g, b) = match nm {
+        nm if nm < 380.0 => return [128, 0, 128],
+        nm if nm < 440.0 => ((440.0 - nm) / 60.0, 0.0, 1.0),
+        nm if nm < 490.0 => (0.0, (nm - 440.0) / 50.0, 1.0),
+        nm if nm < 510.0 => (0.0, 1.0, (510.0 - nm) / 20.0),
+        nm if nm < 580.0 => ((nm - 510.0) / 70.0, 1.0, 0.0),
+        nm if nm < 645.0 => (1.0, (645.0 - nm) / 65.0, 0.0),
+        nm if nm <= 780.0 => (1.0, 0.0, 0.0),
+        _ => return [128, 0, 0],
+    };
+    // dim the colors towards the edges of the visible spectrum
+    let factor = match nm {
+        nm if nm < 420.0 => 0.3 + 0.7 * (nm - 380.0) / 40.0,
+        nm if nm > 700.0 => 0.3 + 0.7 * (780.0 - nm) / 80.0,
+        _ => 1.0,
+    };
+    let scale = |c: f64| (255.0 * c * factor).round() as u8;
+    [scale(r), scale(g), scale(b)]
+}
+
+/// Parse a level, `None` if it is empty.
+fn parse_energy(level: &str, name: &str) -> Result<Option<f64>, String> {
+    match level.trim() {
+        "" => Ok(None),
+        l => match l.parse::<f64>() {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(format!("{}: Level is not a number.", name)),
+        },
+    }
+}
+
+/// Round values for about six ticks of an axis from `min` to `max`.
+pub fn tick_values(min: f64, max: f64) -> Vec<f64> {
+    let raw_step = (max - min) / 6.0;
+    let magnitude = 10f64.powf(raw_step.log10().floor());
+    let tick_step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|s| *s >= raw_step)
+        .unwrap_or(raw_step);
+    let mut ticks = Vec::new();
+    let mut it = (min / tick_step).ceil();
+    while it * tick_step <= max {
+        ticks.push(it * tick_step);
+        it += 1.0;
+    }
+    ticks
+}
+
+/// Energy axis in cm⁻¹ on the left with round tick values.
+fn add_axis(prims: &mut Vec<Primitive>, e_min: f64, e_max: f64, y: &impl Fn(f64) -> f32) {
+    let x = MARGIN_LEFT - 10.0;
+    prims.push(Primitive::Line {
+        from: [x, y(e_min)],
+        to: [x, y(e_max)],
+        ink: Ink::Foreground,
+        width: 1.0,
+        dashed: false,
+    });
+
+    for tick in tick_values(e_min, e_max) {
+        prims.push(Primitive::Line {
+            from: [x - 4.0, y(tick)],
+            to: [x, y(tick)],
+            ink: Ink::Foreground,
+            width: 1.0,
+            dashed: false,
+        });
+        prims.push(Primitive::Text {
+            pos: [x - 6.0, y(tick)],
+            text: format!("{}", tick),
+            size: FONT_SIZE_AXIS,
+            anchor: Anchor::End,
+            ink: Ink::Foreground,
+        });
+    }
+    prims.push(Primitive::Text {
+        pos: [x, MARGIN_TOP / 2.0],
+        text: "cm¯¹".to_string(),
+        size: FONT_SIZE_AXIS,
+        anchor: Anchor::Middle,
+        ink: Ink::Foreground,
+    });
+}
+
+/// Draw the layout with an egui painter into the given rectangle.
+pub fn paint(layout: &SchemeLayout, painter: &egui::Painter, rect: egui::Rect) {
+    let fg = painter.ctx().style().visuals.text_color();
+    let pos = |p: [f32; 2]| rect.min + egui::vec2(p[0], p[1]);
+    let color = |ink: &Ink| match ink {
+        Ink::Foreground => fg,
+        Ink::Rgb([r, g, b]) => egui::Color32::from_rgb(*r, *g, *b),
+    };
+
+    for prim in layout.primitives.iter() {
+        match prim {
+            Primitive::Line {
+                from,
+                to,
+                ink,
+                width,
+                dashed,
+            } => {
+                let stroke = egui::Stroke::new(*width, color(ink));
+                let points = [pos(*from), pos(*to)];
+                match dashed {
+                    true => {
+                        painter.extend(egui::Shape::dashed_line(&points, stroke, 6.0, 4.0));
+                    }
+                    false => {
+                        painter.line_segment(points, stroke);
+                    }
+                }
+            }
+            Primitive::Arrow {
+                from,
+                to,
+                color: [r, g, b],
+                hollow,
+            } => {
+                let col = egui::Color32::from_rgb(*r, *g, *b);
+                let outline: Vec<egui::Pos2> =
+                    arrow_outline(*from, *to).into_iter().map(pos).collect();
+                match hollow {
+                    true => {
+                        painter.add(egui::Shape::closed_line(
+                            outline,
+                            egui::Stroke::new(1.5, col),
+                        ));
+                    }
+                    false => {
+                        // egui only fills convex polygons: draw shaft and head separately
+                        let shaft = vec![outline[0], outline[1], outline[5], outline[6]];
+                        let head = vec![outline[2], outline[3], outline[4]];
+                        for part in [shaft, head] {
+                            painter.add(egui::Shape::convex_polygon(part, col, egui::Stroke::NONE));
+                        }
+                    }
+                }
+            }
+            Primitive::Text {
+                pos: p,
+                text,
+                size,
+                anchor,
+                ink,
+            } => {
+                let align = match anchor {
+                    Anchor::Start => egui::Align2::LEFT_CENTER,
+                    Anchor::Middle => egui::Align2::CENTER_CENTER,
+                    Anchor::End => egui::Align2::RIGHT_CENTER,
+                };
+                painter.text(
+                    pos(*p),
+                    align,
+                    text,
+                    egui::FontId::proportional(*size),
+                    color(ink),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_wavelength_color() {
+    assert_eq!(wavelength_color(300.0), [128, 0, 128]);
+    assert_eq!(wavelength_color(1000.0), [128, 0, 0]);
+    assert_eq!(wavelength_color(530.0)[1], 255);
+    assert_eq!(wavelength_color(650.0), [255, 0, 0]);
+}
+
+#[test]
+fn test_layout_scheme() {
+    let mut app_entries = TemplateApp::default();
+    app_entries.document.scheme_element = crate::Elements::Ti;
+    app_entries.document.scheme_unit = TransitionUnit::NM;
+    app_entries.document.scheme_transitions[0].level = "393.8".to_string();
+    app_entries.document.scheme_transitions[1].level = "500".to_string();
+    app_entries.document.scheme_transitions[1].forbidden = true;
+    app_entries.document.scheme_last_step_to_ip = true;
+
+    let layout = layout_scheme(&app_entries, 400.0, 600.0).unwrap();
+    let arrows: Vec<&Primitive> = layout
+        .primitives
+        .iter()
+        .filter(|p| matches!(p, Primitive::Arrow { .. }))
+        .collect();
+    assert_eq!(arrows.len(), 2);
+    assert!(matches!(arrows[1], Primitive::Arrow { hollow: true, .. }));
+    assert!(layout
+        .primitives
+        .iter()
+        .any(|p| matches!(p, Primitive::Text { text, .. } if text == "393.80 nm")));
+
+    app_entries.document.scheme_transitions[1].level = "abc".to_string();
+    assert_eq!(
+        layout_scheme(&app_entries, 400.0, 600.0),
+        Err("Step 2: Level is not a number.".to_string())
+    );
+}
+
+#[test]
+fn test_layout_targets() {
+    let mut app_entries = TemplateApp::default();
+    app_entries.document.scheme_transitions[0].level = "25000".to_string();
+    app_entries.document.scheme_transitions[0].term_symbol = "3G3".to_string();
+    let layout = layout_scheme(&app_entries, 400.0, 600.0).unwrap();
+    let label = layout
+        .targets
+        .iter()
+        .find(|h| h.target == Target::Label(LabelKind::Term, 0))
+        .unwrap()
+        .clone();
+    assert_eq!(
+        layout.target_at([label.min[0] + 1.0, label.min[1] + 1.0]),
+        Some(Target::Label(LabelKind::Term, 0))
+    );
+    assert_eq!(layout.target_at([1.0, 1.0]), None);
+
+    // dragging a label moves it, and inserting a step moves the offset along
+    app_entries
+        .document
+        .diagram_offsets
+        .get_mut(LabelKind::Term, 0)[1] = 10.0;
+    let moved = layout_scheme(&app_entries, 400.0, 600.0).unwrap();
+    assert!(moved
+        .targets
+        .iter()
+        .any(|h| h.target == label.target && h.min[1] == label.min[1] + 10.0));
+    app_entries.document.diagram_offsets.insert(0);
+    assert_eq!(app_entries.document.diagram_offsets.term[1], [0.0, 10.0]);
+}
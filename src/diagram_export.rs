@@ -0,0 +1,351 @@
+//! Export of the scheme diagram as SVG document and PNG image.
+//!
+//! Both renderers draw the primitives of [`SchemeLayout`], i.e., the same layout that is shown in
+//! the app, with black as the foreground color on a white background.
+
+use std::fmt::Write;
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use tiny_skia::{
+    FillRule, Paint, PathBuilder, Pixmap, PremultipliedColorU8, Stroke, StrokeDash, Transform,
+};
+
+use crate::diagram::{arrow_outline, Anchor, Color, Ink, Primitive, SchemeLayout};
+
+/// Width of exported diagrams in pixels (SVG user units).
+pub const EXPORT_WIDTH: f32 = 500.0;
+/// Height of exported diagrams in pixels (SVG user units).
+pub const EXPORT_HEIGHT: f32 = 700.0;
+
+const FOREGROUND: Color = [0, 0, 0];
+const BACKGROUND: Color = [255, 255, 255];
+
+fn ink_color(ink: &Ink) -> Color {
+    match ink {
+        Ink::Foreground => FOREGROUND,
+        Ink::Rgb(color) => *color,
+    }
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn xml_escape(inp: &str) -> String {
+    inp.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render the layout into an SVG document.
+pub fn render_svg(layout: &SchemeLayout) -> String {
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" viewBox=\"0 0 {w:.0} {h:.0}\">",
+        w = layout.width,
+        h = layout.height
+    );
+    let _ = writeln!(
+        svg,
+        "  <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+        hex(BACKGROUND)
+    );
+
+    for prim in layout.primitives.iter() {
+        let _ = match prim {
+            Primitive::Line {
+                from,
+                to,
+                ink,
+                width,
+                dashed,
+            } => writeln!(
+                svg,
+                "  <line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\" stroke-width=\"{}\"{}/>",
+                from[0],
+                from[1],
+                to[0],
+                to[1],
+                hex(ink_color(ink)),
+                width,
+                if *dashed { " stroke-dasharray=\"6 4\"" } else { "" }
+            ),
+            Primitive::Arrow {
+                from,
+                to,
+                color,
+                hollow,
+            } => {
+                let points: Vec<String> = arrow_outline(*from, *to)
+                    .iter()
+                    .map(|p| format!("{:.2},{:.2}", p[0], p[1]))
+                    .collect();
+                let paint = match hollow {
+                    true => format!("fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"", hex(*color)),
+                    false => format!("fill=\"{}\"", hex(*color)),
+                };
+                writeln!(svg, "  <polygon points=\"{}\" {}/>", points.join(" "), paint)
+            }
+            Primitive::Text {
+                pos,
+                text,
+                size,
+                anchor,
+                ink,
+            } => writeln!(
+                svg,
+                "  <text x=\"{:.2}\" y=\"{:.2}\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"{}\" dominant-baseline=\"middle\" fill=\"{}\">{}</text>",
+                pos[0],
+                pos[1],
+                size,
+                match anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                },
+                hex(ink_color(ink)),
+                xml_escape(text)
+            ),
+        };
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Render the layout into a PNG image, enlarged by `scale` for a higher resolution.
+///
+/// Texts are drawn with the default proportional font of egui, such that the image looks like
+/// the diagram in the app.
+pub fn render_png(layout: &SchemeLayout, scale: f32) -> Result<Vec<u8>, String> {
+    let mut pixmap = Pixmap::new(
+        (layout.width * scale).ceil() as u32,
+        (layout.height * scale).ceil() as u32,
+    )
+    .ok_or("Invalid size of the diagram.".to_string())?;
+    pixmap.fill(tiny_skia::Color::from_rgba8(
+        BACKGROUND[0],
+        BACKGROUND[1],
+        BACKGROUND[2],
+        255,
+    ));
+
+    let fonts = egui::FontDefinitions::default();
+    let font_name = fonts.families[&egui::FontFamily::Proportional]
+        .first()
+        .ok_or("No font available.".to_string())?;
+    let font = FontRef::try_from_slice(&fonts.font_data[font_name].font)
+        .map_err(|e| format!("Cannot read font: {}", e))?;
+
+    let transform = Transform::from_scale(scale, scale);
+    for prim in layout.primitives.iter() {
+        match prim {
+            Primitive::Line {
+                from,
+                to,
+                ink,
+                width,
+                dashed,
+            } => {
+                let mut pb = PathBuilder::new();
+                pb.move_to(from[0], from[1]);
+                pb.line_to(to[0], to[1]);
+                let stroke = Stroke {
+                    width: *width,
+                    dash: match dashed {
+                        true => StrokeDash::new(vec![6.0, 4.0], 0.0),
+                        false => None,
+                    },
+                    ..Default::default()
+                };
+                if let Some(path) = pb.finish() {
+                    pixmap.stroke_path(&path, &paint(ink_color(ink)), &stroke, transform, None);
+                }
+            }
+            Primitive::Arrow {
+                from,
+                to,
+                color,
+                hollow,
+            } => {
+                let mut pb = PathBuilder::new();
+                for (it, p) in arrow_outline(*from, *to).iter().enumerate() {
+                    match it {
+                        0 => pb.move_to(p[0], p[1]),
+                        _ => pb.line_to(p[0], p[1]),
+                    }
+                }
+                pb.close();
+                if let Some(path) = pb.finish() {
+                    match hollow {
+                        true => pixmap.stroke_path(
+                            &path,
+                            &paint(*color),
+                            &Stroke {
+                                width: 1.5,
+                                ..Default::default()
+                            },
+                            transform,
+                            None,
+                        ),
+                        false => pixmap.fill_path(
+                            &path,
+                            &paint(*color),
+                            FillRule::Winding,
+                            transform,
+                            None,
+                        ),
+                    }
+                }
+            }
+            Primitive::Text {
+                pos,
+                text,
+                size,
+                anchor,
+                ink,
+            } => draw_text(
+                &mut pixmap,
+                &font,
+                [pos[0] * scale, pos[1] * scale],
+                text,
+                size * scale,
+                *anchor,
+                ink_color(ink),
+            ),
+        }
+    }
+
+    pixmap
+        .encode_png()
+        .map_err(|e| format!("Cannot encode PNG image: {}", e))
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(color[0], color[1], color[2], 255);
+    paint.anti_alias = true;
+    paint
+}
+
+/// Draw a single line of text, vertically centered at the position, by blending the glyph
+/// coverage onto the (opaque) pixmap.
+fn draw_text(
+    pixmap: &mut Pixmap,
+    font: &FontRef,
+    pos: [f32; 2],
+    text: &str,
+    size: f32,
+    anchor: Anchor,
+    color: Color,
+) {
+    let font = font.as_scaled(PxScale::from(size));
+    let glyphs: Vec<_> = text.chars().map(|c| font.glyph_id(c)).collect();
+    let mut width = 0.0;
+    for (it, id) in glyphs.iter().enumerate() {
+        if it > 0 {
+            width += font.kern(glyphs[it - 1], *id);
+        }
+        width += font.h_advance(*id);
+    }
+
+    let mut x = match anchor {
+        Anchor::Start => pos[0],
+        Anchor::Middle => pos[0] - width / 2.0,
+        Anchor::End => pos[0] - width,
+    };
+    let baseline = pos[1] + (font.ascent() + font.descent()) / 2.0;
+
+    let (w, h) = (pixmap.width() as i32, pixmap.height() as i32);
+    for (it, id) in glyphs.iter().enumerate() {
+        if it > 0 {
+            x += font.kern(glyphs[it - 1], *id);
+        }
+        let glyph = id.with_scale_and_position(size, ab_glyph::point(x, baseline));
+        x += font.h_advance(*id);
+        let outlined = match font.outline_glyph(glyph) {
+            Some(outlined) => outlined,
+            None => continue,
+        };
+        let bounds = outlined.px_bounds();
+        let pixels = pixmap.pixels_mut();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= w || py >= h {
+                return;
+            }
+            let pixel = &mut pixels[(py * w + px) as usize];
+            let blend = |old: u8, new: u8| {
+                (old as f32 * (1.0 - coverage) + new as f32 * coverage).round() as u8
+            };
+            if let Some(blended) = PremultipliedColorU8::from_rgba(
+                blend(pixel.red(), color[0]),
+                blend(pixel.green(), color[1]),
+                blend(pixel.blue(), color[2]),
+                255,
+            ) {
+                *pixel = blended;
+            }
+        });
+    }
+}
+
+/// Compare the rendered SVG with a golden file in `tests/golden`. Set the environment variable
+/// `UPDATE_GOLDEN` to write the files instead.
+#[cfg(test)]
+fn assert_golden(name: &str, svg: &str) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, svg).unwrap();
+    }
+    let golden = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(svg, golden, "SVG differs from {}", path.display());
+}
+
+#[cfg(test)]
+fn golden_scheme() -> crate::TemplateApp {
+    let mut app_entries = crate::TemplateApp::default();
+    app_entries.document.scheme_element = crate::Elements::Ti;
+    app_entries.document.scheme_gs.term_symbol = "3F2".to_string();
+    app_entries.document.scheme_ip_term_symbol = "4F3/2".to_string();
+    app_entries.document.scheme_transitions[0].level = "170.132".to_string();
+    app_entries.document.scheme_transitions[0].term_symbol = "3F3".to_string();
+    app_entries.document.scheme_transitions[0].low_lying = true;
+    app_entries.document.scheme_transitions[1].level = "25388.334".to_string();
+    app_entries.document.scheme_transitions[1].term_symbol = "3G4".to_string();
+    app_entries.document.scheme_transitions[2].level = "50145.6".to_string();
+    app_entries.document.scheme_transitions[2].term_symbol = "3H5".to_string();
+    app_entries.document.scheme_transitions[2].forbidden = true;
+    app_entries.document.scheme_transitions[3].level = "62000".to_string();
+    app_entries
+}
+
+#[cfg(test)]
+#[test]
+fn test_svg_golden() {
+    let mut app_entries = golden_scheme();
+    let layout = crate::diagram::layout_scheme(&app_entries, EXPORT_WIDTH, EXPORT_HEIGHT).unwrap();
+    assert_golden("ti_cm1.svg", &render_svg(&layout));
+
+    app_entries.document.scheme_last_step_to_ip = true;
+    let layout = crate::diagram::layout_scheme(&app_entries, EXPORT_WIDTH, EXPORT_HEIGHT).unwrap();
+    assert_golden("ti_cm1_to_ip.svg", &render_svg(&layout));
+}
+
+#[test]
+fn test_png_export() {
+    let layout =
+        crate::diagram::layout_scheme(&golden_scheme(), EXPORT_WIDTH, EXPORT_HEIGHT).unwrap();
+    let png = render_png(&layout, 2.0).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+    let image = Pixmap::decode_png(&png).unwrap();
+    assert_eq!(image.width(), 2 * EXPORT_WIDTH as u32);
+    // something other than the background was drawn
+    assert!(image.pixels().iter().any(|p| p.red() < 128));
+}
@@ -0,0 +1,198 @@
+//! Several scheme documents in one session, shown as tabs.
+//!
+//! [`TemplateApp::document`] holds the document of the active tab, such that the form, the
+//! diagram, and all exports work on it. The other documents are kept in
+//! [`TemplateApp::documents`], where the entry of the active tab is an empty placeholder. Switching
+//! tabs swaps the active document with its placeholder. Settings of the session, e.g., the loaded
+//! level tables and the name of the submitter, are shared by all documents.
+
+use crate::diagram::LabelOffsets;
+use crate::population::DEFAULT_TEMPERATURE;
+use crate::{
+    Elements, GroundState, Lasers, ReferenceEntry, SaturationCurve, TemplateApp, Transition,
+    TransitionUnit, STEPS,
+};
+
+/// Entries of one scheme.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SchemeDocument {
+    pub notes: String,
+    pub references: Vec<ReferenceEntry>,
+    pub saturation_curves: Vec<SaturationCurve>,
+    pub scheme_element: Elements,
+    pub scheme_gs: GroundState,
+    pub scheme_ip_term_symbol: String,
+    pub scheme_lasers: Lasers,
+    pub scheme_transitions: [Transition; STEPS],
+    pub scheme_last_step_to_ip: bool,
+    pub scheme_unit: TransitionUnit,
+    /// Label positions on the diagram, as dragged by the user.
+    pub diagram_offsets: LabelOffsets,
+    /// Temperature of the atomizer in K.
+    pub temperature: f64,
+    /// Mass number of the isotope, empty for the natural element.
+    pub isotope: String,
+    /// Nuclear spin of the isotope as entered by the user.
+    pub nuclear_spin: String,
+    /// RIMSSchemeDrawer `settings` block of a loaded file, written back unchanged on export.
+    pub drawer_settings: Option<serde_json::Value>,
+    /// Unrecognized top-level keys of a loaded file, written back unchanged on export.
+    pub extra_keys: serde_json::Map<String, serde_json::Value>,
+    /// Unrecognized keys in the scheme of a loaded file, written back unchanged on export.
+    pub extra_scheme_keys: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Default for SchemeDocument {
+    fn default() -> Self {
+        Self {
+            notes: String::new(),
+            references: Vec::new(),
+            saturation_curves: Vec::new(),
+            scheme_element: Elements::H,
+            scheme_gs: GroundState {
+                level: "0".to_owned(),
+                level_unc: String::new(),
+                term_symbol: String::new(),
+            },
+            scheme_ip_term_symbol: String::new(),
+            scheme_lasers: Lasers::TiSa,
+            scheme_transitions: [
+                Transition::new_empty(),
+                Transition::new_empty(),
+                Transition::new_empty(),
+                Transition::new_empty(),
+                Transition::new_empty(),
+                Transition::new_empty(),
+                Transition::new_empty(),
+            ],
+            scheme_last_step_to_ip: false,
+            scheme_unit: TransitionUnit::CM1,
+            diagram_offsets: LabelOffsets::default(),
+            temperature: DEFAULT_TEMPERATURE,
+            isotope: String::new(),
+            nuclear_spin: String::new(),
+            drawer_settings: None,
+            extra_keys: serde_json::Map::new(),
+            extra_scheme_keys: serde_json::Map::new(),
+        }
+    }
+}
+
+impl TemplateApp {
+    /// Make sure that the list of documents contains the active one. The list is empty for a
+    /// fresh start and for states that were saved before tabs existed.
+    pub fn ensure_documents(&mut self) {
+        if self.documents.is_empty() {
+            self.documents.push(SchemeDocument::default());
+            self.active_document = 0;
+        }
+        self.active_document = self.active_document.min(self.documents.len() - 1);
+    }
+
+    /// Number of open documents.
+    pub fn document_count(&self) -> usize {
+        self.documents.len().max(1)
+    }
+
+    /// Tab labels of all documents, e.g., "1: Ti".
+    pub fn document_labels(&self) -> Vec<String> {
+        (0..self.document_count())
+            .map(|it| {
+                let element = match it == self.active_document {
+                    true => &self.document.scheme_element,
+                    false => &self.documents[it].scheme_element,
+                };
+                format!("{}: {:?}", it + 1, element)
+            })
+            .collect()
+    }
+
+    /// Switch to the document at `index`.
+    pub fn select_document(&mut self, index: usize) {
+        self.ensure_documents();
+        if index == self.active_document || index >= self.documents.len() {
+            return;
+        }
+        // park the active document in its placeholder and take out the selected one
+        std::mem::swap(
+            &mut self.document,
+            &mut self.documents[self.active_document],
+        );
+        std::mem::swap(&mut self.document, &mut self.documents[index]);
+        self.active_document = index;
+    }
+
+    /// Open an empty document in a new tab and switch to it.
+    pub fn add_document(&mut self) {
+        self.ensure_documents();
+        self.documents.push(SchemeDocument::default());
+        self.select_document(self.documents.len() - 1);
+    }
+
+    /// Close the document at `index`. The last open document cannot be closed.
+    pub fn close_document(&mut self, index: usize) {
+        self.ensure_documents();
+        let count = self.documents.len();
+        if count < 2 || index >= count {
+            return;
+        }
+        if index == self.active_document {
+            self.select_document(if index + 1 < count {
+                index + 1
+            } else {
+                index - 1
+            });
+        }
+        self.documents.remove(index);
+        if self.active_document > index {
+            self.active_document -= 1;
+        }
+    }
+
+    /// Replace the active document with an empty one. The other documents and the settings of
+    /// the session are kept.
+    pub fn clear_document(&mut self) {
+        self.document = SchemeDocument::default();
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_documents() {
+    let mut app_entries = TemplateApp::default();
+    app_entries.document.scheme_element = Elements::Ti;
+    app_entries.document.notes = "First scheme".to_string();
+    assert_eq!(app_entries.document_count(), 1);
+
+    app_entries.add_document();
+    assert_eq!(app_entries.document_count(), 2);
+    assert_eq!(app_entries.active_document, 1);
+    assert_eq!(app_entries.document.notes, "");
+    assert_eq!(app_entries.document.scheme_gs.level, "0");
+    app_entries.document.scheme_element = Elements::Ti;
+    app_entries.document.notes = "Second scheme".to_string();
+    assert_eq!(app_entries.document_labels(), vec!["1: Ti", "2: Ti"]);
+
+    // documents survive a round trip through the persisted state
+    let state = serde_json::to_string(&app_entries).unwrap();
+    let mut app_entries: TemplateApp = serde_json::from_str(&state).unwrap();
+    app_entries.select_document(0);
+    assert_eq!(app_entries.document.notes, "First scheme");
+
+    app_entries.close_document(0);
+    assert_eq!(app_entries.document_count(), 1);
+    assert_eq!(app_entries.document.notes, "Second scheme");
+    app_entries.close_document(0);
+    assert_eq!(app_entries.document.notes, "Second scheme");
+
+    app_entries.add_document();
+    app_entries.document.notes = "Third scheme".to_string();
+    app_entries.submitted_by = "Jane Doe".to_string();
+    app_entries.clear_document();
+    assert_eq!(app_entries.document.notes, "");
+    assert_eq!(app_entries.submitted_by, "Jane Doe");
+    assert_eq!(app_entries.document_count(), 2);
+    app_entries.select_document(0);
+    assert_eq!(app_entries.document.notes, "Second scheme");
+}
@@ -0,0 +1,233 @@
+//! Resolution of DOI metadata (authors, year, title, ...) for DOI references.
+//!
+//! Metadata is looked up in a local cache file in CSL-JSON format, e.g., as exported from Zotero,
+//! which works offline. On native builds with the `crossref` feature, the Crossref API can be
+//! queried as well.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::extract_doi;
+
+/// Bibliographic metadata of a DOI.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DoiMetadata {
+    /// Display name of the authors, e.g., "Rothe et al."
+    pub author: String,
+    pub year: usize,
+    pub title: Option<String>,
+    pub journal: Option<String>,
+    pub volume: Option<String>,
+    pub pages: Option<String>,
+}
+
+impl DoiMetadata {
+    /// Read metadata from a CSL-JSON item. Crossref returns the same keys in its `message`.
+    fn from_csl(item: &Value) -> Self {
+        let authors = item["author"].as_array().cloned().unwrap_or_default();
+        let mut author = authors
+            .first()
+            .and_then(|a| a["family"].as_str().or(a["literal"].as_str()))
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        if !author.is_empty() && authors.len() > 1 {
+            author.push_str(" et al.");
+        }
+
+        let year = ["issued", "published-print", "published-online"]
+            .iter()
+            .find_map(|key| item[key]["date-parts"][0][0].as_u64())
+            .unwrap_or(0) as usize;
+
+        Self {
+            author,
+            year,
+            title: csl_string(&item["title"]),
+            journal: csl_string(&item["container-title"]),
+            volume: csl_string(&item["volume"]),
+            pages: csl_string(&item["page"]),
+        }
+    }
+}
+
+/// Get a string from a CSL value, which can be a string, a number, or an array of strings.
+fn csl_string(value: &Value) -> Option<String> {
+    let ret = match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(a) => a.first().and_then(|v| v.as_str())?.to_string(),
+        _ => return None,
+    };
+    match ret.trim() {
+        "" => None,
+        s => Some(s.to_string()),
+    }
+}
+
+/// Look up metadata for a DOI.
+pub trait DoiResolver: Send + Sync {
+    /// Return the metadata of the DOI, `None` if the DOI is unknown to this resolver.
+    fn resolve(&self, doi: &str) -> Result<Option<DoiMetadata>, String>;
+}
+
+/// Resolver that looks up DOIs in a local CSL-JSON cache file.
+#[derive(Debug, Default)]
+pub struct CacheResolver {
+    entries: HashMap<String, DoiMetadata>,
+}
+
+impl CacheResolver {
+    /// Create the cache from the content of a JSON file. Either a CSL-JSON array of items with a
+    /// `DOI` key or an object that maps DOIs to CSL-JSON items is accepted.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let json: Value = match serde_json::from_str(text) {
+            Ok(json) => json,
+            Err(e) => return Err(format!("Error reading DOI cache file: {}", e)),
+        };
+
+        let items: Vec<(String, &Value)> = match &json {
+            Value::Array(arr) => arr
+                .iter()
+                .filter_map(|item| Some((item["DOI"].as_str()?.to_string(), item)))
+                .collect(),
+            Value::Object(map) => map.iter().map(|(k, v)| (k.clone(), v)).collect(),
+            _ => return Err("DOI cache file must contain a JSON array or object.".to_string()),
+        };
+
+        let mut entries = HashMap::new();
+        for (doi, item) in items {
+            if let Some(doi) = extract_doi(&doi) {
+                entries.insert(doi, DoiMetadata::from_csl(item));
+            }
+        }
+
+        if entries.is_empty() {
+            return Err("No DOIs found in the DOI cache file.".to_string());
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl DoiResolver for CacheResolver {
+    fn resolve(&self, doi: &str) -> Result<Option<DoiMetadata>, String> {
+        Ok(extract_doi(doi).and_then(|d| self.entries.get(&d).cloned()))
+    }
+}
+
+/// Resolver that queries the Crossref REST API.
+#[cfg(all(feature = "crossref", not(target_arch = "wasm32")))]
+pub struct CrossrefResolver;
+
+#[cfg(all(feature = "crossref", not(target_arch = "wasm32")))]
+impl DoiResolver for CrossrefResolver {
+    fn resolve(&self, doi: &str) -> Result<Option<DoiMetadata>, String> {
+        let url = format!(
+            "https://api.crossref.org/works/{}",
+            urlencoding::encode(doi)
+        );
+        let response = ureq::get(&url)
+            .set(
+                "User-Agent",
+                &format!(
+                    "rimsdb_scheme_submission (mailto:{})",
+                    crate::DB_MAINTAINER_EMAIL
+                ),
+            )
+            .call();
+        let text = match response {
+            Ok(r) => r
+                .into_string()
+                .map_err(|e| format!("Error reading Crossref response: {}", e))?,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(format!("Error querying Crossref: {}", e)),
+        };
+        let json: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Error reading Crossref response: {}", e))?;
+        Ok(Some(DoiMetadata::from_csl(&json["message"])))
+    }
+}
+
+/// Ask several resolvers in turn and return the first metadata found.
+pub struct ResolverChain(pub Vec<Arc<dyn DoiResolver>>);
+
+impl DoiResolver for ResolverChain {
+    fn resolve(&self, doi: &str) -> Result<Option<DoiMetadata>, String> {
+        let mut error = None;
+        for resolver in self.0.iter() {
+            match resolver.resolve(doi) {
+                Ok(Some(meta)) => return Ok(Some(meta)),
+                Ok(None) => (),
+                Err(e) => error = Some(e),
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+const CSL_TEST_CACHE: &str = r#"[
+    {
+        "DOI": "10.1038/ncomms2819",
+        "type": "article-journal",
+        "title": "Measurement of the first ionization potential of astatine by laser ionization spectroscopy",
+        "container-title": "Nature Communications",
+        "volume": "4",
+        "page": "1835",
+        "author": [{"family": "Rothe", "given": "S."}, {"family": "Andreyev", "given": "A. N."}],
+        "issued": {"date-parts": [[2013, 5, 14]]}
+    },
+    {"title": "No DOI, ignored"}
+]"#;
+
+#[cfg(test)]
+#[test]
+fn test_cache_resolver_csl_array() {
+    let cache = CacheResolver::from_json(CSL_TEST_CACHE).unwrap();
+    assert_eq!(cache.len(), 1);
+
+    let meta = cache
+        .resolve("https://doi.org/10.1038/NCOMMS2819")
+        .unwrap()
+        .unwrap();
+    assert_eq!(meta.author, "Rothe et al.");
+    assert_eq!(meta.year, 2013);
+    assert_eq!(meta.journal.as_deref(), Some("Nature Communications"));
+    assert_eq!(meta.volume.as_deref(), Some("4"));
+
+    assert_eq!(cache.resolve("10.500/unknown").unwrap(), None);
+}
+
+#[test]
+fn test_cache_resolver_object() {
+    let json = r#"{"10.500/abc": {"title": ["A title"], "author": [{"literal": "RIMS Collaboration"}], "issued": {"date-parts": [[2020]]}}}"#;
+    let cache = CacheResolver::from_json(json).unwrap();
+    let meta = cache.resolve("10.500/abc").unwrap().unwrap();
+    assert_eq!(meta.author, "RIMS Collaboration");
+    assert_eq!(meta.title.as_deref(), Some("A title"));
+
+    assert!(CacheResolver::from_json("[]").is_err());
+    assert!(CacheResolver::from_json("not json").is_err());
+}
+
+#[test]
+fn test_resolver_chain() {
+    let empty = CacheResolver::default();
+    let cache = CacheResolver::from_json(CSL_TEST_CACHE).unwrap();
+    let chain = ResolverChain(vec![Arc::new(empty), Arc::new(cache)]);
+    let meta = chain.resolve("10.1038/ncomms2819").unwrap().unwrap();
+    assert_eq!(meta.year, 2013);
+}
@@ -0,0 +1,149 @@
+//! Undo and redo of edits to the form.
+//!
+//! The history holds snapshots of the form data of [`TemplateApp`], i.e., the documents of all tabs
+//! and the name of the submitter. Settings of the session, e.g., loaded level tables, are not part
+//! of it. The app records a snapshot whenever an edit is finished, such that typing into a field
+//! gives one entry once the field loses the focus.
+
+use serde_json::{json, Value};
+
+use crate::documents::SchemeDocument;
+use crate::TemplateApp;
+
+/// Maximum number of entries that can be undone.
+const MAX_ENTRIES: usize = 100;
+
+/// Snapshots before and after the recorded state.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Value>,
+    redo: Vec<Value>,
+    /// Last recorded state.
+    current: Option<Value>,
+}
+
+impl History {
+    /// Record `state` if it differs from the last recorded state. This discards the entries that
+    /// could be redone. Returns if an entry was added.
+    pub fn record(&mut self, state: Value) -> bool {
+        match self.current.take() {
+            Some(current) if current != state => {
+                self.undo.push(current);
+                if self.undo.len() > MAX_ENTRIES {
+                    self.undo.remove(0);
+                }
+                self.redo.clear();
+                self.current = Some(state);
+                true
+            }
+            current => {
+                self.current = current.or(Some(state));
+                false
+            }
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// State to restore for undoing the last edit. The shown `state` is recorded first, such that
+    /// an edit that is not finished yet is undone as a whole.
+    pub fn undo(&mut self, state: Value) -> Option<Value> {
+        self.record(state);
+        let previous = self.undo.pop()?;
+        if let Some(current) = self.current.replace(previous.clone()) {
+            self.redo.push(current);
+        }
+        Some(previous)
+    }
+
+    /// State to restore for redoing the last undone edit, `None` if there is none or if the shown
+    /// `state` contains a new edit.
+    pub fn redo(&mut self, state: Value) -> Option<Value> {
+        self.record(state);
+        let next = self.redo.pop()?;
+        if let Some(current) = self.current.replace(next.clone()) {
+            self.undo.push(current);
+        }
+        Some(next)
+    }
+}
+
+impl TemplateApp {
+    /// Form data of all documents.
+    pub fn snapshot(&self) -> Value {
+        json!({
+            "document": serde_json::to_value(&self.document).unwrap_or(Value::Null),
+            "documents": serde_json::to_value(&self.documents).unwrap_or(Value::Null),
+            "active_document": self.active_document,
+            "submitted_by": self.submitted_by,
+        })
+    }
+
+    /// Restore the form data from a snapshot, the settings of the session are kept.
+    pub fn restore(&mut self, mut snapshot: Value) -> Result<(), String> {
+        let error = |e: serde_json::Error| format!("Could not restore the state: {}", e);
+        let document: SchemeDocument =
+            serde_json::from_value(snapshot["document"].take()).map_err(error)?;
+        let documents: Vec<SchemeDocument> =
+            serde_json::from_value(snapshot["documents"].take()).map_err(error)?;
+        self.document = document;
+        self.documents = documents;
+        self.active_document = snapshot["active_document"]
+            .as_u64()
+            .map_or(0, |a| a as usize);
+        self.ensure_documents();
+        self.submitted_by = snapshot["submitted_by"].as_str().unwrap_or("").to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_history() {
+    let mut history = History::default();
+    assert!(!history.record(Value::from(1)));
+    assert!(!history.record(Value::from(1)));
+    assert!(history.record(Value::from(2)));
+    assert!(history.can_undo());
+
+    // an unrecorded edit is undone as a whole
+    assert_eq!(history.undo(Value::from(3)), Some(Value::from(2)));
+    assert_eq!(history.undo(Value::from(2)), Some(Value::from(1)));
+    assert_eq!(history.undo(Value::from(1)), None);
+    assert_eq!(history.redo(Value::from(1)), Some(Value::from(2)));
+
+    // a new edit discards the entries that could be redone
+    assert!(history.record(Value::from(4)));
+    assert!(!history.can_redo());
+    assert_eq!(history.redo(Value::from(4)), None);
+}
+
+#[test]
+fn test_restore() {
+    let mut app_entries = TemplateApp::default();
+    app_entries.document.notes = "First scheme".to_string();
+    app_entries.submitted_by = "Jane Doe".to_string();
+    app_entries.level_tolerance = 2.0;
+    app_entries.add_document();
+    app_entries.document.notes = "Second scheme".to_string();
+    let snapshot = app_entries.snapshot();
+
+    app_entries.clear_document();
+    app_entries.close_document(0);
+    app_entries.submitted_by.clear();
+    app_entries.level_tolerance = 3.0;
+    app_entries.restore(snapshot.clone()).unwrap();
+    // the form data is restored, the settings of the session are kept
+    assert_eq!(app_entries.snapshot(), snapshot);
+    assert_eq!(app_entries.submitted_by, "Jane Doe");
+    assert_eq!(app_entries.level_tolerance, 3.0);
+    assert_eq!(app_entries.document.notes, "Second scheme");
+    app_entries.select_document(0);
+    assert_eq!(app_entries.document.notes, "First scheme");
+}
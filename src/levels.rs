@@ -0,0 +1,429 @@
+//! Tables of known energy levels, read from CSV files as exported from the NIST Atomic Spectra
+//! Database (ASD), and suggestions of levels while typing.
+
+use crate::diagram::{ground_state_energy, level_energies, lower_energy};
+use crate::{TemplateApp, TransitionUnit};
+
+/// Number of suggestions shown while typing.
+const MAX_SUGGESTIONS: usize = 6;
+/// Default tolerance in cm⁻¹ when comparing entered levels with the table.
+pub const DEFAULT_LEVEL_TOLERANCE: f64 = 1.0;
+
+/// A level of the table.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct KnownLevel {
+    pub configuration: String,
+    /// Term as given by NIST, e.g., "a 3F" or "z 3G*" (odd parity).
+    pub term: String,
+    pub j: String,
+    /// Level as written in the table, in cm⁻¹.
+    pub level: String,
+    pub energy: f64,
+}
+
+impl KnownLevel {
+    /// Term symbol as entered in the scheme, e.g., "3F2" for the NIST term "a 3F" with J = 2.
+    /// The letter that labels the term and spaces are dropped, the parity marker `*` is kept.
+    pub fn term_symbol(&self) -> String {
+        let term = match self.term.split_once(' ') {
+            Some((label, term)) if label.chars().all(|c| c.is_ascii_lowercase()) => term,
+            _ => self.term.as_str(),
+        };
+        format!("{}{}", term.replace(' ', ""), self.j)
+    }
+
+    /// One line description of the level for lists.
+    pub fn description(&self) -> String {
+        format!(
+            "{} cm¯¹   {}   {}   J = {}",
+            self.level, self.configuration, self.term, self.j
+        )
+    }
+}
+
+/// Known levels of one element.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LevelTable {
+    pub levels: Vec<KnownLevel>,
+}
+
+/// A suggested level, with the values to fill into the level and term symbol cells.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub label: String,
+    pub level: String,
+    pub term_symbol: String,
+}
+
+/// An entered level that does not agree with the table, with the suggested correction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelMismatch {
+    /// Index of the step, `None` for the ground state.
+    pub step: Option<usize>,
+    pub message: String,
+    /// Corrected level, `None` if only the term symbol is wrong.
+    pub level: Option<String>,
+    /// Corrected term symbol.
+    pub term_symbol: String,
+}
+
+impl LevelTable {
+    /// Read a NIST ASD level table in CSV format.
+    ///
+    /// The columns are identified by their headers ("Configuration", "Term", "J", "Level"), such
+    /// that optional columns of the export can be switched on or off. Spreadsheet-style values
+    /// like `="3d2.4s2"` and markers of theoretical or uncertain levels (`[...]`, `?`) are
+    /// accepted. The ionization limit and rows without a level are skipped.
+    pub fn from_nist_csv(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+        let header = match lines.next() {
+            Some(header) => split_csv_line(header),
+            None => return Err("The level table is empty.".to_string()),
+        };
+        let column = |name: &str| header.iter().position(|h| h.starts_with(name));
+        let (col_conf, col_term, col_j, col_level) = match (
+            column("Configuration"),
+            column("Term"),
+            column("J"),
+            column("Level"),
+        ) {
+            (Some(c), Some(t), Some(j), Some(l)) => (c, t, j, l),
+            _ => {
+                return Err(
+                    "The level table needs the columns Configuration, Term, J, and Level."
+                        .to_string(),
+                )
+            }
+        };
+
+        let mut levels = Vec::new();
+        for line in lines {
+            let fields = split_csv_line(line);
+            let field = |col: usize| fields.get(col).cloned().unwrap_or_default();
+            // levels relative to an unknown one, e.g., "12345.6+x", do not parse and are skipped
+            let level: String = field(col_level)
+                .chars()
+                .filter(|c| !"[]()?".contains(*c) && !c.is_whitespace())
+                .collect();
+            let energy = match level.parse::<f64>() {
+                Ok(energy) if field(col_term) != "Limit" => energy,
+                _ => continue,
+            };
+            levels.push(KnownLevel {
+                configuration: field(col_conf),
+                term: field(col_term),
+                j: field(col_j),
+                level,
+                energy,
+            });
+        }
+
+        if levels.is_empty() {
+            return Err("No levels found in the level table.".to_string());
+        }
+        Ok(Self { levels })
+    }
+
+    /// Levels closest to the given energy, closest first.
+    pub fn nearest(&self, energy: f64, count: usize) -> Vec<&KnownLevel> {
+        let mut levels: Vec<&KnownLevel> = self.levels.iter().collect();
+        levels.sort_by(|a, b| {
+            (a.energy - energy)
+                .abs()
+                .total_cmp(&(b.energy - energy).abs())
+        });
+        levels.truncate(count);
+        levels
+    }
+
+    /// Suggestions for a level cell. The input is an energy in cm⁻¹ or, if `unit` is nm, the
+    /// wavelength of the transition from the level at `lower` energy.
+    pub fn suggest_levels(
+        &self,
+        input: &str,
+        unit: &TransitionUnit,
+        lower: Option<f64>,
+    ) -> Vec<Suggestion> {
+        let value = match input.trim().parse::<f64>() {
+            Ok(value) if value > 0.0 || *unit == TransitionUnit::CM1 => value,
+            _ => return Vec::new(),
+        };
+        let (energy, lower) = match (unit, lower) {
+            (TransitionUnit::CM1, _) => (value, None),
+            (TransitionUnit::NM, Some(lower)) => (lower + 1e7 / value, Some(lower)),
+            (TransitionUnit::NM, None) => return Vec::new(),
+        };
+
+        self.nearest(energy, MAX_SUGGESTIONS)
+            .into_iter()
+            .filter(|lvl| lower.map_or(true, |lower| lvl.energy > lower))
+            .map(|lvl| self.suggestion(lvl, unit, lower))
+            .collect()
+    }
+
+    /// Suggestions for a term symbol cell: levels whose term symbol starts with the input,
+    /// closest to `energy` first if given. The level is filled in as for [`Self::suggest_levels`].
+    pub fn suggest_terms(
+        &self,
+        input: &str,
+        energy: Option<f64>,
+        unit: &TransitionUnit,
+        lower: Option<f64>,
+    ) -> Vec<Suggestion> {
+        let input = input.trim().to_lowercase();
+        if input.is_empty() {
+            return Vec::new();
+        }
+        let mut levels: Vec<&KnownLevel> = self
+            .levels
+            .iter()
+            .filter(|lvl| lvl.term_symbol().to_lowercase().starts_with(&input))
+            .filter(|lvl| lower.map_or(true, |lower| lvl.energy > lower))
+            .collect();
+        if let Some(energy) = energy {
+            levels.sort_by(|a, b| {
+                (a.energy - energy)
+                    .abs()
+                    .total_cmp(&(b.energy - energy).abs())
+            });
+        }
+        levels
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|lvl| self.suggestion(lvl, unit, lower))
+            .collect()
+    }
+
+    /// Compare the levels and term symbols of the scheme with the table.
+    ///
+    /// A level is flagged if no known level lies within `tolerance` (in cm⁻¹), the closest known
+    /// level is then suggested. A term symbol is flagged if none of the known levels within the
+    /// tolerance has this term, the term of the closest one is then suggested. Empty term symbols
+    /// and levels that cannot be converted to energies are not checked.
+    pub fn check_scheme(&self, app_entries: &TemplateApp, tolerance: f64) -> Vec<LevelMismatch> {
+        let mut mismatches = Vec::new();
+        if let Ok(energy) = ground_state_energy(app_entries) {
+            mismatches.extend(self.check_level(
+                None,
+                energy,
+                &app_entries.document.scheme_gs.term_symbol,
+                &TransitionUnit::CM1,
+                None,
+                tolerance,
+            ));
+        }
+
+        for (it, trans) in app_entries.document.scheme_transitions.iter().enumerate() {
+            let energy = match level_energies(app_entries, it + 1)
+                .ok()
+                .and_then(|energies| energies[it])
+            {
+                Some(energy) => energy,
+                None => continue,
+            };
+            // low-lying states are always given in cm¯¹
+            let (unit, lower) = match trans.low_lying {
+                true => (&TransitionUnit::CM1, None),
+                false => (
+                    &app_entries.document.scheme_unit,
+                    lower_energy(app_entries, it),
+                ),
+            };
+            mismatches.extend(self.check_level(
+                Some(it),
+                energy,
+                &trans.term_symbol,
+                unit,
+                lower,
+                tolerance,
+            ));
+        }
+        mismatches
+    }
+
+    fn check_level(
+        &self,
+        step: Option<usize>,
+        energy: f64,
+        term_symbol: &str,
+        unit: &TransitionUnit,
+        lower: Option<f64>,
+        tolerance: f64,
+    ) -> Option<LevelMismatch> {
+        let name = match step {
+            Some(it) => format!("Step {}", it + 1),
+            None => "Ground state".to_string(),
+        };
+        let closest = self.nearest(energy, 1).into_iter().next()?;
+        if (closest.energy - energy).abs() > tolerance {
+            let fix = self.suggestion(closest, unit, lower);
+            return Some(LevelMismatch {
+                step,
+                message: format!(
+                    "{}: No known level within {} cm¯¹ of {:.3} cm¯¹, the closest is {}.",
+                    name,
+                    tolerance,
+                    energy,
+                    closest.description()
+                ),
+                level: Some(fix.level),
+                term_symbol: fix.term_symbol,
+            });
+        }
+
+        let entered = normalize_term(term_symbol);
+        if entered.is_empty() {
+            return None;
+        }
+        let matches = self
+            .levels
+            .iter()
+            .filter(|lvl| (lvl.energy - energy).abs() <= tolerance)
+            .any(|lvl| entered.ends_with(&normalize_term(&lvl.term_symbol())));
+        match matches {
+            true => None,
+            false => Some(LevelMismatch {
+                step,
+                message: format!(
+                    "{}: Term symbol {} does not match the known level {}.",
+                    name,
+                    term_symbol,
+                    closest.description()
+                ),
+                level: None,
+                term_symbol: closest.term_symbol(),
+            }),
+        }
+    }
+
+    /// Suggestion of a known level, with the level as energy or, if `unit` is nm, as the
+    /// wavelength of the transition from the level at `lower` energy.
+    fn suggestion(
+        &self,
+        lvl: &KnownLevel,
+        unit: &TransitionUnit,
+        lower: Option<f64>,
+    ) -> Suggestion {
+        let level = match (unit, lower) {
+            (TransitionUnit::NM, Some(lower)) => format!("{:.4}", 1e7 / (lvl.energy - lower)),
+            _ => lvl.level.clone(),
+        };
+        Suggestion {
+            label: lvl.description(),
+            level,
+            term_symbol: lvl.term_symbol(),
+        }
+    }
+}
+
+/// Term symbol reduced for comparisons: LaTeX markup, spaces, parentheses, and parity markers are
+/// dropped, such that, e.g., "(^{3}F_{2})" and "3F2" agree.
+fn normalize_term(term: &str) -> String {
+    term.chars()
+        .filter(|c| !" ^{}_()*°".contains(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Split a line of a CSV file into its fields, removing quotes and the `=` prefix that NIST adds
+/// to keep spreadsheets from interpreting the values.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+        .into_iter()
+        .map(|f| {
+            let f = f.trim();
+            let f = f.strip_prefix('=').unwrap_or(f);
+            f.trim_matches('"').trim().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+const NIST_TEST_TABLE: &str = r#""Configuration","Term","J","Prefix","Level (cm-1)","Suffix","Uncertainty (cm-1)","Reference"
+"=""3d2.4s2""","=""a 3F""","=""2""","","=""0.0000""","","",""
+"=""3d2.4s2""","=""a 3F""","=""3""","","=""170.1325""","","",""
+"=""3d2.4s2""","=""a 3F""","=""4""","","=""386.8738""","","",""
+"=""3d2.4s4p""","=""z 3G*""","=""3""","","=""[25388.33]""","","",""
+"=""3d3.4p""","=""5G*""","=""2""","","=""12345.6+x""","","",""
+"Ti II (4F<3/2>)","Limit","","","=""55072.5""","","",""
+"#;
+
+#[cfg(test)]
+#[test]
+fn test_parse_nist_csv() {
+    let table = LevelTable::from_nist_csv(NIST_TEST_TABLE).unwrap();
+    // the limit and the relative level "12345.6+x" are skipped
+    assert_eq!(table.levels.len(), 4);
+    assert!(table.levels.iter().all(|l| l.term != "5G*"));
+    assert_eq!(table.levels[1].configuration, "3d2.4s2");
+    assert_eq!(table.levels[1].term_symbol(), "3F3");
+    assert_eq!(table.levels[3].level, "25388.33");
+    assert_eq!(table.levels[3].term_symbol(), "3G*3");
+
+    assert!(LevelTable::from_nist_csv("a,b\n1,2").is_err());
+}
+
+#[test]
+fn test_suggestions() {
+    let table = LevelTable::from_nist_csv(NIST_TEST_TABLE).unwrap();
+
+    let suggestions = table.suggest_levels("172", &TransitionUnit::CM1, None);
+    assert_eq!(suggestions[0].level, "170.1325");
+    assert_eq!(suggestions[0].term_symbol, "3F3");
+
+    // the wavelength from the ground state is filled in for nm
+    let suggestions = table.suggest_levels("394", &TransitionUnit::NM, Some(0.0));
+    assert_eq!(suggestions[0].level, format!("{:.4}", 1e7 / 25388.33));
+    assert_eq!(suggestions.len(), 3);
+
+    let suggestions = table.suggest_terms("3f", Some(400.0), &TransitionUnit::CM1, None);
+    assert_eq!(suggestions.len(), 3);
+    assert_eq!(suggestions[0].level, "386.8738");
+}
+
+#[test]
+fn test_check_scheme() {
+    let table = LevelTable::from_nist_csv(NIST_TEST_TABLE).unwrap();
+    let mut app_entries = TemplateApp::default();
+    app_entries.document.scheme_gs.term_symbol = "^{3}F_{2}".to_string();
+    app_entries.document.scheme_transitions[0].level = "170.2".to_string();
+    app_entries.document.scheme_transitions[0].term_symbol = "3F3".to_string();
+    app_entries.document.scheme_transitions[0].low_lying = true;
+    app_entries.document.scheme_transitions[1].level = "25388.5".to_string();
+    app_entries.document.scheme_transitions[1].term_symbol = "3G3".to_string();
+    assert_eq!(table.check_scheme(&app_entries, 1.0), Vec::new());
+
+    // wrong level and wrong term, checked relative to the low-lying state in nm
+    app_entries.document.scheme_unit = TransitionUnit::NM;
+    app_entries.document.scheme_transitions[1].level = "395.0".to_string();
+    app_entries.document.scheme_transitions[1].term_symbol = "3G4".to_string();
+    app_entries.document.scheme_transitions[0].term_symbol = "3F4".to_string();
+    let mismatches = table.check_scheme(&app_entries, 1.0);
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0].step, Some(0));
+    assert_eq!(mismatches[0].level, None);
+    assert_eq!(mismatches[0].term_symbol, "3F3");
+    assert_eq!(mismatches[1].step, Some(1));
+    assert_eq!(
+        mismatches[1].level,
+        Some(format!("{:.4}", 1e7 / (25388.33 - 170.2)))
+    );
+    assert_eq!(mismatches[1].term_symbol, "3G*3");
+}
@@ -1,15 +1,41 @@
 use std::borrow::ToOwned;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
-use serde_json::{json, ser::to_string_pretty, Value};
+use regex::Regex;
+
+use serde_json::{json, ser::to_string_pretty, Map, Value};
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 use urlencoding::encode;
 
-mod app;
+use saturation::{fit_saturation, DerivedQuantities, LaserParameters};
+use strength::StrengthInput;
 
-pub use app::TemplateApp;
+mod app;
+pub mod batch;
+mod bibliography;
+pub mod broadening;
+pub mod cli;
+pub mod diagram;
+pub mod diagram_export;
+pub mod documents;
+mod doi_resolver;
+mod history;
+pub mod levels;
+mod migrations;
+pub mod population;
+pub mod saturation;
+pub mod schema;
+pub mod strength;
+
+pub use app::{TemplateApp, STEPS};
+#[cfg(all(feature = "crossref", not(target_arch = "wasm32")))]
+pub use doi_resolver::CrossrefResolver;
+pub use doi_resolver::{CacheResolver, DoiMetadata, DoiResolver, ResolverChain};
+pub use migrations::FORMAT_VERSION;
 
 const DB_MAINTAINER_EMAIL: &str = "reto@galactic-forensics.space";
 
@@ -356,11 +382,133 @@ impl Elements {
             Elements::Hs => 61000.0,
         }
     }
+
+    /// Standard atomic weight in u. For elements without stable isotopes, the mass number of the
+    /// longest-lived isotope is used, as given in brackets in the periodic table.
+    pub fn atomic_weight(&self) -> f64 {
+        match self {
+            Elements::H => 1.008,
+            Elements::He => 4.0026,
+            Elements::Li => 6.94,
+            Elements::Be => 9.0122,
+            Elements::B => 10.81,
+            Elements::C => 12.011,
+            Elements::N => 14.007,
+            Elements::O => 15.999,
+            Elements::F => 18.998,
+            Elements::Ne => 20.180,
+            Elements::Na => 22.990,
+            Elements::Mg => 24.305,
+            Elements::Al => 26.982,
+            Elements::Si => 28.085,
+            Elements::P => 30.974,
+            Elements::S => 32.06,
+            Elements::Cl => 35.45,
+            Elements::Ar => 39.948,
+            Elements::K => 39.098,
+            Elements::Ca => 40.078,
+            Elements::Sc => 44.956,
+            Elements::Ti => 47.867,
+            Elements::V => 50.942,
+            Elements::Cr => 51.996,
+            Elements::Mn => 54.938,
+            Elements::Fe => 55.845,
+            Elements::Co => 58.933,
+            Elements::Ni => 58.693,
+            Elements::Cu => 63.546,
+            Elements::Zn => 65.38,
+            Elements::Ga => 69.723,
+            Elements::Ge => 72.630,
+            Elements::As => 74.922,
+            Elements::Se => 78.971,
+            Elements::Br => 79.904,
+            Elements::Kr => 83.798,
+            Elements::Rb => 85.468,
+            Elements::Sr => 87.62,
+            Elements::Y => 88.906,
+            Elements::Zr => 91.224,
+            Elements::Nb => 92.906,
+            Elements::Mo => 95.95,
+            Elements::Tc => 98.0,
+            Elements::Ru => 101.07,
+            Elements::Rh => 102.91,
+            Elements::Pd => 106.42,
+            Elements::Ag => 107.87,
+            Elements::Cd => 112.41,
+            Elements::In => 114.82,
+            Elements::Sn => 118.71,
+            Elements::Sb => 121.76,
+            Elements::Te => 127.60,
+            Elements::I => 126.90,
+            Elements::Xe => 131.29,
+            Elements::Cs => 132.91,
+            Elements::Ba => 137.33,
+            Elements::La => 138.91,
+            Elements::Ce => 140.12,
+            Elements::Pr => 140.91,
+            Elements::Nd => 144.24,
+            Elements::Pm => 145.0,
+            Elements::Sm => 150.36,
+            Elements::Eu => 151.96,
+            Elements::Gd => 157.25,
+            Elements::Tb => 158.93,
+            Elements::Dy => 162.50,
+            Elements::Ho => 164.93,
+            Elements::Er => 167.26,
+            Elements::Tm => 168.93,
+            Elements::Yb => 173.05,
+            Elements::Lu => 174.97,
+            Elements::Hf => 178.49,
+            Elements::Ta => 180.95,
+            Elements::W => 183.84,
+            Elements::Re => 186.21,
+            Elements::Os => 190.23,
+            Elements::Ir => 192.22,
+            Elements::Pt => 195.08,
+            Elements::Au => 196.97,
+            Elements::Hg => 200.59,
+            Elements::Tl => 204.38,
+            Elements::Pb => 207.2,
+            Elements::Bi => 208.98,
+            Elements::Po => 209.0,
+            Elements::At => 210.0,
+            Elements::Rn => 222.0,
+            Elements::Fr => 223.0,
+            Elements::Ra => 226.0,
+            Elements::Ac => 227.0,
+            Elements::Th => 232.04,
+            Elements::Pa => 231.04,
+            Elements::U => 238.03,
+            Elements::Np => 237.0,
+            Elements::Pu => 244.0,
+            Elements::Am => 243.0,
+            Elements::Cm => 247.0,
+            Elements::Bk => 247.0,
+            Elements::Cf => 251.0,
+            Elements::Es => 252.0,
+            Elements::Fm => 257.0,
+            Elements::Md => 258.0,
+            Elements::No => 259.0,
+            Elements::Lr => 262.0,
+            Elements::Rf => 267.0,
+            Elements::Db => 268.0,
+            Elements::Sg => 269.0,
+            Elements::Bh => 270.0,
+            Elements::Hs => 269.0,
+        }
+    }
+
+    pub fn atomic_number(&self) -> u32 {
+        Elements::iter().position(|e| &e == self).unwrap_or(0) as u32 + 1
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct GroundState {
     pub level: String,
+    /// Uncertainty of the level in cm⁻¹, empty if not given.
+    #[serde(default)]
+    pub level_unc: String,
     pub term_symbol: String,
 }
 
@@ -379,6 +527,53 @@ impl GroundState {
     }
 }
 
+/// Check an uncertainty, which is optional but must be a non-negative number if given.
+fn get_uncertainty(unc: &str, name: &str) -> Result<String, String> {
+    match unc.trim() {
+        "" => Ok(String::new()),
+        u if u.parse::<f64>().is_ok_and(|u| u >= 0.0) => Ok(u.to_owned()),
+        _ => Err(format!(
+            "Uncertainty of the {} is not a valid number.",
+            name
+        )),
+    }
+}
+
+/// Split a value in concise notation into the value and its uncertainty, e.g., "25123.45(3)"
+/// into "25123.45" and "0.03", or "1.23(4)e7" into "1.23e7" and "4e5". Returns `None` if the
+/// input is not in concise notation.
+pub fn split_concise(input: &str) -> Option<(String, String)> {
+    let (value, rest) = input.trim().split_once('(')?;
+    let (unc, exponent) = rest.split_once(')')?;
+    let value = value.trim();
+    let exponent = match exponent.trim() {
+        "" => None,
+        e => Some(e.strip_prefix(['e', 'E'])?.parse::<i32>().ok()?),
+    };
+    if value.parse::<f64>().is_err() || unc.parse::<f64>().is_err() {
+        return None;
+    }
+
+    // an uncertainty with a decimal point is given in the unit of the value
+    let decimals = value.split_once('.').map_or(0, |(_, d)| d.len()) as i32;
+    let unc = match (unc.contains('.'), exponent) {
+        (true, None) => unc.to_owned(),
+        (true, Some(e)) => format!("{}e{}", unc, e),
+        (false, None) if decimals == 0 => unc.parse::<u64>().ok()?.to_string(),
+        (false, None) => format!(
+            "{:.*}",
+            decimals as usize,
+            unc.parse::<u64>().ok()? as f64 / 10f64.powi(decimals)
+        ),
+        (false, Some(e)) => format!("{}e{}", unc.parse::<u64>().ok()?, e - decimals),
+    };
+    let value = match exponent {
+        Some(e) => format!("{}e{}", value, e),
+        None => value.to_owned(),
+    };
+    Some((value, unc))
+}
+
 #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum Lasers {
     TiSa,
@@ -436,6 +631,8 @@ pub struct SaturationCurve {
     pub xdat_unc: Option<Vec<f64>>,
     pub ydat: Vec<f64>,
     pub ydat_unc: Option<Vec<f64>>,
+    #[serde(default)]
+    pub laser: LaserParameters,
 }
 
 impl SaturationCurve {
@@ -491,6 +688,7 @@ impl SaturationCurve {
             xdat_unc,
             ydat,
             ydat_unc,
+            laser: LaserParameters::default(),
         })
     }
 
@@ -538,20 +736,44 @@ impl SaturationCurve {
 #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct Transition {
     pub level: String,
+    /// Uncertainty of the level in the unit of the level, empty if not given.
+    #[serde(default)]
+    pub level_unc: String,
     pub term_symbol: String,
     pub transition_strength: String,
+    /// Uncertainty of the transition strength in s⁻¹, empty if not given.
+    #[serde(default)]
+    pub strength_unc: String,
     pub low_lying: bool,
     pub forbidden: bool,
+    /// Quantity that the transition strength was calculated from, if the converter was used.
+    #[serde(default)]
+    pub strength_input: Option<StrengthInput>,
+    /// Where the values of the step come from, if given.
+    #[serde(default)]
+    pub source: Option<StepSource>,
+    /// DOI, URL, or id of a reference for the values of the step.
+    #[serde(default)]
+    pub source_reference: String,
+    /// Free text note on the step, empty if not given.
+    #[serde(default)]
+    pub note: String,
 }
 
 impl Transition {
     fn new_empty() -> Self {
         Self {
             level: String::new(),
+            level_unc: String::new(),
             term_symbol: String::new(),
             transition_strength: String::new(),
+            strength_unc: String::new(),
             low_lying: false,
             forbidden: false,
+            strength_input: None,
+            source: None,
+            source_reference: String::new(),
+            note: String::new(),
         }
     }
 
@@ -582,11 +804,110 @@ impl Transition {
     }
 }
 
+/// Insert an empty step at `index` and move the following steps up by one. Fails if the last
+/// step is already in use.
+fn insert_transition(transitions: &mut [Transition], index: usize) -> Result<(), String> {
+    match transitions.last() {
+        Some(last) if last.level.is_empty() => (),
+        _ => return Err("All steps are in use, no step can be inserted.".to_string()),
+    }
+    if index >= transitions.len() {
+        return Err("No step can be inserted after the last one.".to_string());
+    }
+    transitions[index..].rotate_right(1);
+    transitions[index] = Transition::new_empty();
+    Ok(())
+}
+
+/// Where the values of a step come from.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize, EnumIter)]
+pub enum StepSource {
+    Literature,
+    ThisWork,
+    Calculated,
+    Estimated,
+}
+
+impl fmt::Display for StepSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StepSource::Literature => write!(f, "Literature"),
+            StepSource::ThisWork => write!(f, "This work"),
+            StepSource::Calculated => write!(f, "Calculated"),
+            StepSource::Estimated => write!(f, "Estimated"),
+        }
+    }
+}
+
+impl FromStr for StepSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace([' ', '_'], "").as_str() {
+            "literature" => Ok(StepSource::Literature),
+            "thiswork" => Ok(StepSource::ThisWork),
+            "calculated" => Ok(StepSource::Calculated),
+            "estimated" => Ok(StepSource::Estimated),
+            _ => Err(format!("Step source {} not found.", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize, EnumIter)]
+pub enum ReferenceKind {
+    #[default]
+    Article,
+    Thesis,
+    Proceedings,
+    Dataset,
+}
+
+impl ReferenceKind {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReferenceKind::Article => write!(f, "Article"),
+            ReferenceKind::Thesis => write!(f, "Thesis"),
+            ReferenceKind::Proceedings => write!(f, "Proceedings"),
+            ReferenceKind::Dataset => write!(f, "Dataset"),
+        }
+    }
+}
+
+impl FromStr for ReferenceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "article" => Ok(ReferenceKind::Article),
+            "thesis" => Ok(ReferenceKind::Thesis),
+            "proceedings" => Ok(ReferenceKind::Proceedings),
+            "dataset" => Ok(ReferenceKind::Dataset),
+            _ => Err(format!("Reference kind {} not found.", s)),
+        }
+    }
+}
+
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct ReferenceEntry {
     id: String,
     author: String,
     year: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    journal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    volume: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pages: Option<String>,
+    #[serde(default, skip_serializing_if = "ReferenceKind::is_default")]
+    kind: ReferenceKind,
 }
 
 impl ReferenceEntry {
@@ -595,6 +916,11 @@ impl ReferenceEntry {
             id: doi.into(),
             author: String::new(),
             year: 0,
+            title: None,
+            journal: None,
+            volume: None,
+            pages: None,
+            kind: ReferenceKind::default(),
         }
     }
 
@@ -603,16 +929,157 @@ impl ReferenceEntry {
             id: url.into(),
             author: author.into(),
             year,
+            title: None,
+            journal: None,
+            volume: None,
+            pages: None,
+            kind: ReferenceKind::default(),
+        }
+    }
+
+    /// Add the optional bibliographic details. Empty strings are stored as `None`.
+    fn with_details(
+        mut self,
+        title: &str,
+        journal: &str,
+        volume: &str,
+        pages: &str,
+        kind: ReferenceKind,
+    ) -> Self {
+        self.title = non_empty(title);
+        self.journal = non_empty(journal);
+        self.volume = non_empty(volume);
+        self.pages = non_empty(pages);
+        self.kind = kind;
+        self
+    }
+
+    /// Check if the reference is a DOI for which author or year are still unknown.
+    fn needs_doi_metadata(&self) -> bool {
+        is_doi(&self.id) && (self.author.is_empty() || self.year == 0)
+    }
+
+    /// Fill in resolved DOI metadata. Data that was already entered is not overwritten.
+    fn apply_doi_metadata(&mut self, meta: &DoiMetadata) {
+        if self.author.is_empty() {
+            self.author.clone_from(&meta.author);
+        }
+        if self.year == 0 {
+            self.year = meta.year;
         }
+        for (field, value) in [
+            (&mut self.title, &meta.title),
+            (&mut self.journal, &meta.journal),
+            (&mut self.volume, &meta.volume),
+            (&mut self.pages, &meta.pages),
+        ] {
+            if field.is_none() {
+                field.clone_from(value);
+            }
+        }
+    }
+
+    /// Entry of the exported references. DOI references are written with an empty author and
+    /// year 0 as the database expects, resolved metadata is only kept by the app.
+    fn to_export_json(&self) -> Value {
+        let mut entry = serde_json::to_value(self).unwrap_or(Value::Null);
+        if is_doi(&self.id) {
+            entry["author"] = Value::from("");
+            entry["year"] = Value::from(0);
+        }
+        entry
     }
 
     fn get_url(&self) -> String {
-        if self.author.is_empty() && self.year == 0 {
+        if is_doi(&self.id) {
             format!("https://doi.org/{}", self.id)
         } else {
             self.id.clone()
         }
     }
+
+    /// Short citation, e.g., "Rothe et al. (2013)". Falls back to the id if no author is known.
+    pub fn short_citation(&self) -> String {
+        match (self.author.is_empty(), self.year) {
+            (true, _) => self.id.clone(),
+            (false, 0) => self.author.clone(),
+            (false, year) => format!("{} ({})", self.author, year),
+        }
+    }
+
+    /// Full citation without the link, e.g.,
+    /// "Rothe et al. (2013). Measurement of the first ionization potential of astatine. Nat. Commun. 4, 1835."
+    fn citation_body(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if !self.author.is_empty() {
+            parts.push(self.short_citation());
+        }
+        if let Some(title) = &self.title {
+            parts.push(title.clone());
+        }
+
+        let volume_pages = match (&self.volume, &self.pages) {
+            (Some(v), Some(p)) => format!("{}, {}", v, p),
+            (Some(v), None) => v.clone(),
+            (None, Some(p)) => p.clone(),
+            (None, None) => String::new(),
+        };
+        let source = match self.kind {
+            ReferenceKind::Article => match &self.journal {
+                Some(j) => format!("{} {}", j, volume_pages),
+                None => volume_pages,
+            },
+            ReferenceKind::Proceedings => match &self.journal {
+                Some(j) if volume_pages.is_empty() => format!("In: {}", j),
+                Some(j) => format!("In: {}, {}", j, volume_pages),
+                None => volume_pages,
+            },
+            ReferenceKind::Thesis | ReferenceKind::Dataset => match &self.journal {
+                Some(j) => format!("{}, {}", self.kind, j),
+                None => self.kind.to_string(),
+            },
+        };
+        if !source.trim().is_empty() {
+            parts.push(source.trim().to_string());
+        }
+
+        parts
+            .iter()
+            .map(|p| match p.ends_with('.') {
+                true => p.clone(),
+                false => format!("{}.", p),
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Full citation including the link to the reference.
+    pub fn full_citation(&self) -> String {
+        let body = self.citation_body();
+        match body.is_empty() {
+            true => self.get_url(),
+            false => format!("{} {}", body, self.get_url()),
+        }
+    }
+
+    /// Full citation as a Markdown list item with the link to the reference.
+    pub fn markdown_citation(&self) -> String {
+        let body = self.citation_body();
+        match body.is_empty() {
+            true => format!("- <{}>", self.get_url()),
+            false => format!("- {} <{}>", body, self.get_url()),
+        }
+    }
+}
+
+/// Create a Markdown formatted list of all references.
+fn create_references_markdown(references: &[ReferenceEntry]) -> String {
+    let mut ret = "# References\n\n".to_string();
+    for reference in references {
+        ret.push_str(&reference.markdown_citation());
+        ret.push('\n');
+    }
+    ret
 }
 
 /// Create email content and link and fill it
@@ -645,56 +1112,284 @@ fn create_gh_issue(body: &str, element: &Elements) -> String {
     )
 }
 
-/// Create a JSON output string from the input data in the mask.
-fn create_json_output(app_entries: &TemplateApp) -> Result<String, String> {
-    let scheme_unit_json = match app_entries.scheme_unit {
+/// File formats that can be read and written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubmissionFormat {
+    /// Database submission with the scheme under `rims_scheme`, plus notes, references, etc.
+    Database,
+    /// Plain RIMSSchemeDrawer configuration with the scheme under `scheme`.
+    Drawer,
+}
+
+impl SubmissionFormat {
+    /// Detect the format of a JSON file by looking for the "rims_scheme" or "scheme" key.
+    pub fn detect(json: &Value) -> Option<Self> {
+        if json["rims_scheme"].is_object() {
+            Some(SubmissionFormat::Database)
+        } else if json["scheme"].is_object() {
+            Some(SubmissionFormat::Drawer)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for SubmissionFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubmissionFormat::Database => write!(f, "database"),
+            SubmissionFormat::Drawer => write!(f, "drawer"),
+        }
+    }
+}
+
+impl FromStr for SubmissionFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "database" | "db" => Ok(SubmissionFormat::Database),
+            "drawer" | "rimsschemedrawer" => Ok(SubmissionFormat::Drawer),
+            _ => Err(format!(
+                "Unknown format '{}', use 'database' or 'drawer'.",
+                s
+            )),
+        }
+    }
+}
+
+/// Information collected while loading a file.
+#[derive(Debug, Default, PartialEq)]
+pub struct LoadReport {
+    /// Format version of the file before migration.
+    pub format_version: u64,
+    /// Descriptions of the migrations that were applied to the file.
+    pub migrations: Vec<String>,
+    /// Violations of the submission schema, only checked for database entries.
+    pub schema_errors: Vec<String>,
+    /// Fields that were defaulted, coerced to the expected type, or ignored while importing.
+    pub fields: Vec<String>,
+}
+
+impl LoadReport {
+    /// Summary of the report to display, empty if there is nothing to report.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.migrations.is_empty() {
+            parts.push(format!(
+                "Upgraded file from format version {}:\n{}",
+                self.format_version,
+                self.migrations.join("\n")
+            ));
+        }
+        if !self.schema_errors.is_empty() {
+            parts.push(format!(
+                "File does not match the submission schema:\n{}",
+                self.schema_errors.join("\n")
+            ));
+        }
+        if !self.fields.is_empty() {
+            parts.push(format!("Import report:\n{}", self.fields.join("\n")));
+        }
+        parts.join("\n")
+    }
+}
+
+/// Load a submission or RIMSSchemeDrawer file into a new, otherwise empty, app state.
+pub fn load_submission(text: &str) -> Result<(TemplateApp, LoadReport), String> {
+    let mut app_entries = TemplateApp::default();
+    app_entries.rimsschemedrawer_in = text.to_owned();
+    let report = load_config_file(&mut app_entries)?;
+    Ok((app_entries, report))
+}
+
+/// Create the JSON object of the scheme itself, i.e., the part RIMSSchemeDrawer can read.
+fn create_scheme_json(app_entries: &TemplateApp) -> Result<Value, String> {
+    let scheme_unit_json = match app_entries.document.scheme_unit {
         TransitionUnit::NM => "nm",
         TransitionUnit::CM1 => "cm<sup>-1</sup>",
     };
 
     // error checking
-    if app_entries.scheme_transitions[0].level.is_empty() {
+    if app_entries.document.scheme_transitions[0].level.is_empty() {
         return Err("No transitions entered: Please add at least one step.".into());
-    } else if app_entries.submitted_by.is_empty() {
-        return Err("Please enter your name.".into());
     }
 
-    // create the json file
-    let mut json_out = json!({
-        "notes": replace_linebreak(&app_entries.notes),
-        "rims_scheme": {
-            "scheme": {
-                "element": format!("{:?}", app_entries.scheme_element),
-                "lasers": app_entries.scheme_lasers.to_string(),
-                "last_step_to_ip": app_entries.scheme_last_step_to_ip,
-                "gs_term": strip_latex_dollars(&app_entries.scheme_gs.term_symbol),
-                "gs_level": app_entries.scheme_gs.get_level()?,
-                "ip_term": strip_latex_dollars(&app_entries.scheme_ip_term_symbol),
-                "unit": scheme_unit_json,
-            },
-        },
-        "references": app_entries.references,
-        "submitted_by": app_entries.submitted_by,
+    let mut scheme = json!({
+        "element": format!("{:?}", app_entries.document.scheme_element),
+        "lasers": app_entries.document.scheme_lasers.to_string(),
+        "last_step_to_ip": app_entries.document.scheme_last_step_to_ip,
+        "gs_term": strip_latex_dollars(&app_entries.document.scheme_gs.term_symbol),
+        "gs_level": app_entries.document.scheme_gs.get_level()?,
+        "ip_term": strip_latex_dollars(&app_entries.document.scheme_ip_term_symbol),
+        "unit": scheme_unit_json,
     });
+    let gs_level_unc = get_uncertainty(
+        &app_entries.document.scheme_gs.level_unc,
+        "ground state level",
+    )?;
+    if !gs_level_unc.is_empty() {
+        scheme["gs_level_unc"] = Value::from(gs_level_unc);
+    }
 
-    for (it, val) in app_entries.scheme_transitions.iter().enumerate() {
+    for (it, val) in app_entries.document.scheme_transitions.iter().enumerate() {
         let level = val.get_level()?;
         let term_symbol_stripped = strip_latex_dollars(&val.term_symbol);
         if !level.is_empty() {
-            json_out["rims_scheme"]["scheme"][format!("step_level{}", it)] = Value::from(level);
-            json_out["rims_scheme"]["scheme"][format!("step_term{}", it)] =
-                Value::from(term_symbol_stripped);
-            json_out["rims_scheme"]["scheme"][format!("trans_strength{}", it)] =
-                Value::from(val.get_transition_strength()?);
-            json_out["rims_scheme"]["scheme"][format!("step_forbidden{}", it)] =
-                Value::from(val.forbidden);
-            json_out["rims_scheme"]["scheme"][format!("step_lowlying{}", it)] =
-                Value::from(val.low_lying);
+            scheme[format!("step_level{}", it)] = Value::from(level);
+            scheme[format!("step_term{}", it)] = Value::from(term_symbol_stripped);
+            scheme[format!("trans_strength{}", it)] = Value::from(val.get_transition_strength()?);
+            scheme[format!("step_forbidden{}", it)] = Value::from(val.forbidden);
+            scheme[format!("step_lowlying{}", it)] = Value::from(val.low_lying);
+            for (key, unc, name) in [
+                ("step_level_unc", &val.level_unc, "level"),
+                (
+                    "trans_strength_unc",
+                    &val.strength_unc,
+                    "transition strength",
+                ),
+            ] {
+                let unc = get_uncertainty(unc, name)?;
+                if !unc.is_empty() {
+                    scheme[format!("{}{}", key, it)] = Value::from(unc);
+                }
+            }
+            if let Some(input) = &val.strength_input {
+                scheme[format!("trans_strength_input{}", it)] = input.to_json();
+            }
+            if let Some(source) = &val.source {
+                scheme[format!("step_source{}", it)] = Value::from(format!("{:?}", source));
+            }
+            for (key, text) in [
+                ("step_reference", val.source_reference.trim()),
+                ("step_note", val.note.trim()),
+            ] {
+                if !text.is_empty() {
+                    scheme[format!("{}{}", key, it)] = Value::from(text);
+                }
+            }
         }
     }
+    insert_extra_keys(&mut scheme, &app_entries.document.extra_scheme_keys);
+
+    Ok(scheme)
+}
+
+/// Figure settings that RIMSSchemeDrawer uses if none were loaded with the scheme.
+pub fn default_drawer_settings() -> Value {
+    json!({
+        "fig_width": 5.0,
+        "fig_height": 8.0,
+        "fs_title": 14,
+        "fs_axes": 12,
+        "fs_axes_labels": 14,
+        "fs_labels": 12,
+        "headspace": 2700,
+        "arrow_width": 0.2,
+        "arrow_head_width": 0.6,
+        "prec_level": 0,
+        "prec_strength": 1,
+        "ip_label_pos": "Top",
+        "show_cm-1_axis": true,
+        "show_eV_axis": true,
+        "show_forbidden_transitions": "x-out",
+        "show_transition_strength": true,
+        "line_breakup": true,
+        "plot_title": "",
+    })
+}
+
+/// Add keys that were kept from a loaded file, unless the app writes a key of the same name.
+fn insert_extra_keys(json: &mut Value, extra: &Map<String, Value>) {
+    if let Some(obj) = json.as_object_mut() {
+        for (key, val) in extra {
+            if !obj.contains_key(key) {
+                obj.insert(key.clone(), val.clone());
+            }
+        }
+    }
+}
+
+/// Keys of a scheme that the app reads itself, all others are kept as they are.
+fn is_known_scheme_key(key: &str, steps: usize) -> bool {
+    const KNOWN: [&str; 8] = [
+        "element",
+        "lasers",
+        "last_step_to_ip",
+        "gs_term",
+        "gs_level",
+        "gs_level_unc",
+        "ip_term",
+        "unit",
+    ];
+    const STEP_PREFIXES: [&str; 11] = [
+        "step_level",
+        "step_level_unc",
+        "step_term",
+        "trans_strength",
+        "trans_strength_unc",
+        "trans_strength_input",
+        "step_source",
+        "step_reference",
+        "step_note",
+        "step_forbidden",
+        "step_lowlying",
+    ];
+    KNOWN.contains(&key)
+        || STEP_PREFIXES.iter().any(|prefix| {
+            key.strip_prefix(prefix)
+                .and_then(|it| it.parse::<usize>().ok())
+                .is_some_and(|it| it < steps && key == format!("{}{}", prefix, it))
+        })
+}
+
+/// Create a RIMSSchemeDrawer configuration string from the input data in the mask.
+/// Settings that were loaded with the scheme are kept, otherwise the default settings are used.
+pub fn create_drawer_output(app_entries: &TemplateApp) -> Result<String, String> {
+    let mut json_out = json!({
+        "scheme": create_scheme_json(app_entries)?,
+        "settings": match &app_entries.document.drawer_settings {
+            Some(settings) => settings.clone(),
+            None => default_drawer_settings(),
+        },
+    });
+    insert_extra_keys(&mut json_out, &app_entries.document.extra_keys);
+
+    match to_string_pretty(&json_out) {
+        Ok(json) => Ok(json),
+        Err(e) => Err(format!("Error creating JSON output: {}", e).to_string()),
+    }
+}
+
+/// Create a JSON output string from the input data in the mask.
+pub fn create_json_output(app_entries: &TemplateApp) -> Result<String, String> {
+    let scheme = create_scheme_json(app_entries)?;
+
+    // error checking
+    if app_entries.submitted_by.is_empty() {
+        return Err("Please enter your name.".into());
+    }
+
+    check_references(&app_entries.document.references)?;
+
+    // create the json file
+    let mut json_out = json!({
+        "format_version": FORMAT_VERSION,
+        "notes": replace_linebreak(&app_entries.document.notes),
+        "rims_scheme": {
+            "scheme": scheme,
+        },
+        "references": app_entries
+            .document
+            .references
+            .iter()
+            .map(ReferenceEntry::to_export_json)
+            .collect::<Vec<Value>>(),
+        "submitted_by": app_entries.submitted_by,
+    });
 
     let mut sat_curves_arr: Vec<Value> = Vec::new();
-    for val in app_entries.saturation_curves.iter() {
+    for val in app_entries.document.saturation_curves.iter() {
         let sat_unit_json = match val.units {
             SaturationCurveUnit::WCM2 => "W * cm^-2",
             SaturationCurveUnit::W => "W",
@@ -718,10 +1413,23 @@ fn create_json_output(app_entries: &TemplateApp) -> Result<String, String> {
             Some(y) => json_tmp["data"]["y_err"] = Value::from(y.clone()),
             None => (),
         }
+        if !val.laser.is_empty() {
+            json_tmp["laser"] = val.laser.to_json();
+        }
+        if val.fit {
+            if let Ok(fit) = fit_saturation(val) {
+                let derived = DerivedQuantities::new(fit.saturation, &val.units, &val.laser);
+                json_tmp["derived"] = derived.to_json(&fit);
+            }
+        }
 
         sat_curves_arr.push(json_tmp);
     }
     json_out["saturation_curves"] = Value::from(sat_curves_arr);
+    if let Some(settings) = &app_entries.document.drawer_settings {
+        json_out["rims_scheme"]["settings"] = settings.clone();
+    }
+    insert_extra_keys(&mut json_out, &app_entries.document.extra_keys);
 
     match to_string_pretty(&json_out) {
         Ok(json) => Ok(json),
@@ -729,6 +1437,23 @@ fn create_json_output(app_entries: &TemplateApp) -> Result<String, String> {
     }
 }
 
+/// Check that all reference ids are valid, normalized, and unique.
+fn check_references(references: &[ReferenceEntry]) -> Result<(), String> {
+    for (it, reference) in references.iter().enumerate() {
+        let rid = reference.id.parse::<ReferenceId>()?;
+        if rid.as_str() != reference.id {
+            return Err(format!(
+                "Reference '{}' is not normalized, please edit it and update it.",
+                reference.id
+            ));
+        }
+        if references[..it].iter().any(|r| r.id == reference.id) {
+            return Err(format!("Reference '{}' is listed twice.", reference.id));
+        }
+    }
+    Ok(())
+}
+
 /// Take a data string and transfer it to a f64 Vector.
 fn data_string_to_vec_f64(data: &str, name: &str) -> Result<Vec<f64>, String> {
     let mut x_data: Vec<f64> = Vec::new();
@@ -742,36 +1467,84 @@ fn data_string_to_vec_f64(data: &str, name: &str) -> Result<Vec<f64>, String> {
     Ok(x_data)
 }
 
-fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
-    let config_json: Value = match serde_json::from_str(&app_entries.rimsschemedrawer_in) {
+fn load_config_file(app_entries: &mut TemplateApp) -> Result<LoadReport, String> {
+    let mut config_json: Value = match serde_json::from_str(&app_entries.rimsschemedrawer_in) {
         Ok(json) => json,
         Err(e) => return Err(format!("Error reading JSON file: {}", e).to_string()),
     };
 
-    // Let's check what file we have here by looking for the "rims_scheme" key.
-    let scheme;
-    if config_json["rims_scheme"].is_object() {
-        scheme = config_json["rims_scheme"]["scheme"].clone();
-    } else if config_json["scheme"].is_object() {
-        scheme = config_json["scheme"].clone();
-    } else {
-        return Err("No 'rims_scheme' or 'scheme' key found in the JSON file.".to_string());
+    // Bring RIMSSchemeDrawer files and older database entries to the current layout.
+    let is_database = SubmissionFormat::detect(&config_json) == Some(SubmissionFormat::Database);
+    let (format_version, migrations) = migrations::migrate(&mut config_json)?;
+    let schema_errors = match is_database {
+        true => schema::validate_submission_json(&config_json)
+            .iter()
+            .map(|e| e.to_string())
+            .collect(),
+        false => Vec::new(),
+    };
+    let mut report = LoadReport {
+        format_version,
+        migrations,
+        schema_errors,
+        fields: Vec::new(),
+    };
+    let fields = &mut report.fields;
+    let scheme = config_json["rims_scheme"]["scheme"].clone();
+
+    // Keep the drawer settings and everything the app does not know for the export
+    const KNOWN_KEYS: [&str; 6] = [
+        "format_version",
+        "notes",
+        "rims_scheme",
+        "references",
+        "saturation_curves",
+        "submitted_by",
+    ];
+    app_entries.document.drawer_settings = match &config_json["rims_scheme"]["settings"] {
+        Value::Null => None,
+        settings => Some(settings.clone()),
+    };
+    app_entries.document.extra_keys = Map::new();
+    app_entries.document.extra_scheme_keys = Map::new();
+    if let Some(obj) = config_json.as_object() {
+        for (key, val) in obj
+            .iter()
+            .filter(|(k, _)| !KNOWN_KEYS.contains(&k.as_str()))
+        {
+            fields.push(format!("{}: unknown key, kept unchanged for export.", key));
+            app_entries
+                .document
+                .extra_keys
+                .insert(key.clone(), val.clone());
+        }
+    }
+    if let Some(obj) = scheme.as_object() {
+        let steps = app_entries.document.scheme_transitions.len();
+        for (key, val) in obj.iter().filter(|(k, _)| !is_known_scheme_key(k, steps)) {
+            fields.push(format!("{}: unknown key, kept unchanged for export.", key));
+            app_entries
+                .document
+                .extra_scheme_keys
+                .insert(key.clone(), val.clone());
+        }
     }
 
     // Load the scheme data
-    app_entries.scheme_element = match scheme["element"].as_str() {
+    app_entries.document.scheme_element = match scheme["element"].as_str() {
         Some(e) => e.parse::<Elements>()?,
         None => return Err("No element found in the JSON file.".to_string()),
     };
 
-    app_entries.scheme_gs = GroundState {
-        level: scheme["gs_level"].as_str().unwrap_or("0").to_owned(),
-        term_symbol: scheme["gs_term"].as_str().unwrap_or("").to_owned(),
+    app_entries.document.scheme_gs = GroundState {
+        level: read_numeric(&scheme, "gs_level", "0", fields),
+        level_unc: read_numeric(&scheme, "gs_level_unc", "", fields),
+        term_symbol: read_text(&scheme, "gs_term", fields),
     };
 
-    app_entries.scheme_ip_term_symbol = scheme["ip_term"].as_str().unwrap_or("").into();
+    app_entries.document.scheme_ip_term_symbol = read_text(&scheme, "ip_term", fields);
 
-    app_entries.scheme_lasers = match scheme["lasers"].as_str() {
+    app_entries.document.scheme_lasers = match scheme["lasers"].as_str() {
         Some(l) => match l {
             "Ti:Sa" => Lasers::TiSa,
             "Dye" => Lasers::Dye,
@@ -781,9 +1554,9 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
         None => return Err("No laser type found in the JSON file.".to_string()),
     };
 
-    app_entries.scheme_last_step_to_ip = scheme["last_step_to_ip"].as_bool().unwrap_or(false);
+    app_entries.document.scheme_last_step_to_ip = read_bool(&scheme, "last_step_to_ip", fields);
 
-    let mut scheme_transitions: [Transition; 7] = [
+    let mut scheme_transitions: [Transition; STEPS] = [
         Transition::new_empty(),
         Transition::new_empty(),
         Transition::new_empty(),
@@ -793,33 +1566,69 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
         Transition::new_empty(),
     ];
 
-    for it in 0..scheme_transitions.len() {
-        if !scheme[format!("step_level{}", it)].is_null() {
-            scheme_transitions[it] = Transition {
-                level: scheme[format!("step_level{}", it)]
-                    .as_str()
-                    .unwrap_or("")
-                    .to_owned(),
-                term_symbol: scheme[format!("step_term{}", it)]
-                    .as_str()
-                    .unwrap_or("")
-                    .to_owned(),
-                transition_strength: scheme[format!("trans_strength{}", it)]
-                    .as_str()
-                    .unwrap_or("")
-                    .to_owned(),
-                low_lying: scheme[format!("step_lowlying{}", it)]
-                    .as_bool()
-                    .unwrap_or(false),
-                forbidden: scheme[format!("step_forbidden{}", it)]
-                    .as_bool()
-                    .unwrap_or(false),
-            };
+    for (it, transition) in scheme_transitions.iter_mut().enumerate() {
+        let step_keys = [
+            format!("step_term{}", it),
+            format!("trans_strength{}", it),
+            format!("step_lowlying{}", it),
+            format!("step_forbidden{}", it),
+            format!("trans_strength_input{}", it),
+            format!("step_level_unc{}", it),
+            format!("trans_strength_unc{}", it),
+            format!("step_source{}", it),
+            format!("step_reference{}", it),
+            format!("step_note{}", it),
+        ];
+        if scheme[format!("step_level{}", it)].is_null() {
+            for key in step_keys.iter().filter(|k| !scheme[k.as_str()].is_null()) {
+                fields.push(format!(
+                    "{}: ignored, since step_level{} is missing.",
+                    key, it
+                ));
+            }
+            continue;
         }
+        *transition = Transition {
+            level: read_numeric(&scheme, &format!("step_level{}", it), "", fields),
+            level_unc: read_numeric(&scheme, &step_keys[5], "", fields),
+            term_symbol: read_text(&scheme, &step_keys[0], fields),
+            transition_strength: read_numeric(&scheme, &step_keys[1], "", fields),
+            strength_unc: read_numeric(&scheme, &step_keys[6], "", fields),
+            low_lying: read_bool(&scheme, &step_keys[2], fields),
+            forbidden: read_bool(&scheme, &step_keys[3], fields),
+            strength_input: match &scheme[&step_keys[4]] {
+                Value::Null => None,
+                entry => {
+                    let input = StrengthInput::from_json(entry);
+                    if input.is_none() {
+                        fields.push(format!(
+                            "{}: ignored invalid value {}.",
+                            step_keys[4], entry
+                        ));
+                    }
+                    input
+                }
+            },
+            source: match &scheme[&step_keys[7]] {
+                Value::Null => None,
+                entry => match entry.as_str().map(|s| s.parse::<StepSource>()) {
+                    Some(Ok(source)) => Some(source),
+                    _ => {
+                        fields.push(format!(
+                            "{}: ignored invalid value {}.",
+                            step_keys[7], entry
+                        ));
+                        None
+                    }
+                },
+            },
+            source_reference: read_text(&scheme, &step_keys[8], fields),
+            note: read_text(&scheme, &step_keys[9], fields),
+        };
     }
-    app_entries.scheme_transitions = scheme_transitions;
+    app_entries.document.scheme_transitions = scheme_transitions;
 
-    app_entries.scheme_unit = match scheme["unit"].as_str() {
+    app_entries.document.scheme_unit = match scheme["unit"].as_str() {
         Some(u) => match u {
             "nm" => TransitionUnit::NM,
             _ => TransitionUnit::CM1,
@@ -828,7 +1637,7 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
     };
 
     // Load Notes if they are there
-    app_entries.notes = config_json["notes"].as_str().unwrap_or("").into();
+    app_entries.document.notes = config_json["notes"].as_str().unwrap_or("").into();
 
     // Load References if they are there
     let refs = config_json["references"].as_array();
@@ -839,20 +1648,72 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
                 Some(s) => s,
                 None => continue,
             };
+            // Store normalized ids where possible, invalid ones are reported on export.
+            let rid = match rid.parse::<ReferenceId>() {
+                Ok(id) => id.as_str().to_owned(),
+                Err(_) => rid.to_owned(),
+            };
+            if references.iter().any(|e| e.id == rid) {
+                continue;
+            }
+            let rid = rid.as_str();
             let rauth = r["author"].as_str().unwrap_or("");
-            let ryear = match r["year"].as_u64() {
-                Some(y) => y as usize,
-                None => 0_usize,
+            let ryear = match &r["year"] {
+                Value::Null => 0,
+                Value::String(y) if y.trim().parse::<usize>().is_ok() => {
+                    fields.push(format!(
+                        "references/{}/year: converted text \"{}\" to a number.",
+                        rid, y
+                    ));
+                    y.trim().parse::<usize>().unwrap_or(0)
+                }
+                y => match y.as_u64() {
+                    Some(y) => y as usize,
+                    None => {
+                        fields.push(format!(
+                            "references/{}/year: ignored invalid value {}.",
+                            rid, y
+                        ));
+                        0
+                    }
+                },
             };
 
-            if rauth.is_empty() && ryear == 0 {
-                references.push(ReferenceEntry::new_from_doi(rid))
+            // DOIs are recognized by their id, an empty author and year 0 by convention
+            let entry = if is_doi(rid) {
+                ReferenceEntry {
+                    author: rauth.to_string(),
+                    year: ryear,
+                    ..ReferenceEntry::new_from_doi(rid)
+                }
+            } else if rauth.is_empty() && ryear == 0 {
+                ReferenceEntry::new_from_doi(rid)
             } else {
-                references.push(ReferenceEntry::new_from_url(rid, rauth, ryear))
-            }
+                ReferenceEntry::new_from_url(rid, rauth, ryear)
+            };
+            let rkind = match &r["kind"] {
+                Value::Null => ReferenceKind::default(),
+                k => match k.as_str().map(|k| k.parse::<ReferenceKind>()) {
+                    Some(Ok(kind)) => kind,
+                    _ => {
+                        fields.push(format!(
+                            "references/{}/kind: ignored invalid value {}.",
+                            rid, k
+                        ));
+                        ReferenceKind::default()
+                    }
+                },
+            };
+            references.push(entry.with_details(
+                r["title"].as_str().unwrap_or(""),
+                r["journal"].as_str().unwrap_or(""),
+                r["volume"].as_str().unwrap_or(""),
+                r["pages"].as_str().unwrap_or(""),
+                rkind,
+            ));
         }
     };
-    app_entries.references = references;
+    app_entries.document.references = references;
 
     // Load saturation curves if they are there
     let sats = config_json["saturation_curves"].as_array();
@@ -870,7 +1731,7 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
             };
             let fit = sat["fit"].as_bool().unwrap_or(true);
             let xdat = match sat["data"]["x"].as_array() {
-                Some(x) => json_array_to_f64(x)?,
+                Some(x) => json_array_to_f64(x, fields)?,
                 None => {
                     return Err(
                         format!("No saturation x data found in the JSON file for {title}.")
@@ -879,7 +1740,7 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
                 }
             };
             let ydat = match sat["data"]["y"].as_array() {
-                Some(y) => json_array_to_f64(y)?,
+                Some(y) => json_array_to_f64(y, fields)?,
                 None => {
                     return Err(
                         format!("No saturation y data found in the JSON file for {title}.")
@@ -888,11 +1749,11 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
                 }
             };
             let xunc = match sat["data"]["x_err"].as_array() {
-                Some(x) => Some(json_array_to_f64(x)?),
+                Some(x) => Some(json_array_to_f64(x, fields)?),
                 None => None,
             };
             let yunc = match sat["data"]["y_err"].as_array() {
-                Some(y) => Some(json_array_to_f64(y)?),
+                Some(y) => Some(json_array_to_f64(y, fields)?),
                 None => None,
             };
             saturation_curves.push(SaturationCurve {
@@ -904,15 +1765,16 @@ fn load_config_file(app_entries: &mut TemplateApp) -> Result<(), String> {
                 xdat_unc: xunc,
                 ydat,
                 ydat_unc: yunc,
+                laser: LaserParameters::from_json(&sat["laser"]),
             });
         }
     }
-    app_entries.saturation_curves = saturation_curves;
+    app_entries.document.saturation_curves = saturation_curves;
 
     // Load Notes if they are there
     app_entries.submitted_by = config_json["submitted_by"].as_str().unwrap_or("").into();
 
-    Ok(())
+    Ok(report)
 }
 
 /// Take a data string and split it into a vector of strings according to a list of delimiters.
@@ -925,10 +1787,23 @@ fn split_data_string(data: &str) -> Vec<String> {
     xsplit
 }
 
-fn json_array_to_f64(data: &Vec<Value>) -> Result<Vec<f64>, String> {
+/// Convert a JSON array of numbers to floats. Numeric strings are accepted as well and reported.
+fn json_array_to_f64(data: &Vec<Value>, fields: &mut Vec<String>) -> Result<Vec<f64>, String> {
     let mut x_data: Vec<f64> = Vec::new();
     for xd in data {
-        let xf64 = xd.as_f64();
+        let xf64 = match xd {
+            Value::String(x) => {
+                let x = x.trim().parse::<f64>().ok();
+                if x.is_some() {
+                    fields.push(format!(
+                        "Saturation curve data: converted text {} to a number.",
+                        xd
+                    ));
+                }
+                x
+            }
+            _ => xd.as_f64(),
+        };
         match xf64 {
             Some(x) => x_data.push(x),
             None => return Err("None-numeric value fount in data.".to_string()),
@@ -937,10 +1812,158 @@ fn json_array_to_f64(data: &Vec<Value>) -> Result<Vec<f64>, String> {
     Ok(x_data)
 }
 
+/// Read a numeric scheme field, which is stored as a string but may be given as a JSON number.
+/// Missing fields are set to `default`, invalid ones are ignored. Both are noted in `fields`.
+fn read_numeric(scheme: &Value, key: &str, default: &str, fields: &mut Vec<String>) -> String {
+    match &scheme[key] {
+        Value::Null => {
+            if !default.is_empty() {
+                fields.push(format!("{}: missing, set to {}.", key, default));
+            }
+            default.to_owned()
+        }
+        Value::String(v) => {
+            let v = v.trim();
+            if !v.is_empty() && v.parse::<f64>().is_err() {
+                fields.push(format!("{}: \"{}\" is not a number.", key, v));
+            }
+            v.to_owned()
+        }
+        Value::Number(v) => {
+            fields.push(format!("{}: converted number {} to text.", key, v));
+            v.to_string()
+        }
+        v => {
+            fields.push(format!("{}: ignored invalid value {}.", key, v));
+            default.to_owned()
+        }
+    }
+}
+
+/// Read a text field of the scheme. Numbers are converted, other values are ignored.
+fn read_text(scheme: &Value, key: &str, fields: &mut Vec<String>) -> String {
+    match &scheme[key] {
+        Value::Null => String::new(),
+        Value::String(v) => v.to_owned(),
+        Value::Number(v) => {
+            fields.push(format!("{}: converted number {} to text.", key, v));
+            v.to_string()
+        }
+        v => {
+            fields.push(format!("{}: ignored invalid value {}.", key, v));
+            String::new()
+        }
+    }
+}
+
+/// Read a boolean field of the scheme, which is `false` if missing. The strings "true" and
+/// "false" are converted, other values are ignored.
+fn read_bool(scheme: &Value, key: &str, fields: &mut Vec<String>) -> bool {
+    match &scheme[key] {
+        Value::Null => false,
+        Value::Bool(v) => *v,
+        Value::String(v) if v.trim() == "true" || v.trim() == "false" => {
+            fields.push(format!("{}: converted text \"{}\" to a boolean.", key, v));
+            v.trim() == "true"
+        }
+        v => {
+            fields.push(format!("{}: ignored invalid value {}.", key, v));
+            false
+        }
+    }
+}
+
 /// Check if a given string is a doi or not.
-/// DOIs contain one slash.
+/// Plain DOIs as well as DOI links and `doi:` prefixed DOIs are recognized.
 fn is_doi(inp: &str) -> bool {
-    inp.chars().filter(|c| *c == '/').count() == 1
+    extract_doi(inp).is_some()
+}
+
+/// Extract a DOI from a plain DOI, a `doi.org` / `dx.doi.org` link, or a `doi:` prefixed string.
+///
+/// The DOI is validated against the pattern recommended by Crossref, relaxed to also accept short
+/// registrant codes, and returned in lowercase since DOIs are case insensitive.
+fn extract_doi(inp: &str) -> Option<String> {
+    static RE_PREFIX: OnceLock<Regex> = OnceLock::new();
+    static RE_DOI: OnceLock<Regex> = OnceLock::new();
+    let re_prefix = RE_PREFIX.get_or_init(|| {
+        Regex::new(r"(?i)^(?:(?:https?://)?(?:www\.|dx\.)?doi\.org/|doi:\s*)").unwrap()
+    });
+    let stripped = re_prefix.replace(inp.trim(), "");
+    let decoded = urlencoding::decode(&stripped).ok()?;
+
+    let re_doi = RE_DOI.get_or_init(|| {
+        Regex::new(r"(?i)^10\.\d{1,9}(?:\.\d+)*/[-._;()/:a-z0-9<>\[\]]+$").unwrap()
+    });
+    match re_doi.is_match(&decoded) {
+        true => Some(decoded.to_lowercase()),
+        false => None,
+    }
+}
+
+/// Check if a given string is a valid http(s) URL.
+fn is_url(inp: &str) -> bool {
+    static RE_URL: OnceLock<Regex> = OnceLock::new();
+    let re = RE_URL.get_or_init(|| {
+        Regex::new(
+            r"(?i)^https?://[a-z0-9](?:[a-z0-9-]*[a-z0-9])?(?:\.[a-z0-9](?:[a-z0-9-]*[a-z0-9])?)+(?::\d+)?(?:[/?#]\S*)?$",
+        )
+        .unwrap()
+    });
+    re.is_match(inp)
+}
+
+/// Identifier of a reference, normalized such that the same paper always gets the same id.
+#[derive(Debug, PartialEq)]
+pub enum ReferenceId {
+    Doi(String),
+    Url(String),
+}
+
+impl ReferenceId {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ReferenceId::Doi(doi) => doi,
+            ReferenceId::Url(url) => url,
+        }
+    }
+}
+
+impl FromStr for ReferenceId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("Reference is empty".to_string());
+        }
+
+        if let Some(doi) = extract_doi(s) {
+            return Ok(ReferenceId::Doi(doi));
+        }
+
+        if !is_url(s) {
+            return Err(format!("'{}' is neither a DOI nor a valid URL.", s));
+        }
+
+        // Scheme and host are case insensitive, the rest of the URL is not.
+        let (scheme, rest) = s.split_once("://").unwrap();
+        let host_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        Ok(ReferenceId::Url(format!(
+            "{}://{}{}",
+            scheme.to_lowercase(),
+            rest[..host_end].to_lowercase(),
+            &rest[host_end..]
+        )))
+    }
+}
+
+/// Return `None` for an empty (or whitespace only) string, otherwise the trimmed string.
+fn non_empty(inp: &str) -> Option<String> {
+    match inp.trim() {
+        "" => None,
+        s => Some(s.to_string()),
+    }
 }
 
 /// Replace linebreaks with two spaces and a linebreak.
@@ -996,8 +2019,47 @@ fn test_parse_element() {
 fn test_is_doi() {
     let doi_good = "10.500/123456789";
     assert!(is_doi(doi_good));
-    let doi_bad = "https://doi.org/10.500/123456789";
-    assert!(!is_doi(doi_bad));
+    assert!(is_doi("10.1000/abc/def"));
+    assert!(is_doi("https://doi.org/10.1/x"));
+    assert!(!is_doi("https://example.com/10.500/123456789"));
+    assert!(!is_doi("10.500"));
+    assert!(!is_doi("11.500/123456789"));
+}
+
+#[test]
+fn test_extract_doi() {
+    let expected = Some("10.1103/physrevlett.1".to_string());
+    assert_eq!(extract_doi("10.1103/PhysRevLett.1"), expected);
+    assert_eq!(
+        extract_doi("https://doi.org/10.1103/PhysRevLett.1"),
+        expected
+    );
+    assert_eq!(
+        extract_doi("http://dx.doi.org/10.1103/PhysRevLett.1"),
+        expected
+    );
+    assert_eq!(extract_doi("doi: 10.1103/PhysRevLett.1"), expected);
+    assert_eq!(
+        extract_doi("https://doi.org/10.1103%2FPhysRevLett.1"),
+        expected
+    );
+}
+
+#[test]
+fn test_reference_id() {
+    assert_eq!(
+        "https://doi.org/10.500/ABC".parse::<ReferenceId>(),
+        Ok(ReferenceId::Doi("10.500/abc".to_string()))
+    );
+    assert_eq!(
+        "HTTPS://Example.COM/Paper?id=A".parse::<ReferenceId>(),
+        Ok(ReferenceId::Url(
+            "https://example.com/Paper?id=A".to_string()
+        ))
+    );
+    assert!("example.com/paper".parse::<ReferenceId>().is_err());
+    assert!("https://not a url".parse::<ReferenceId>().is_err());
+    assert!("".parse::<ReferenceId>().is_err());
 }
 
 #[test]
@@ -1053,3 +2115,324 @@ fn test_data_string_to_vec_f64() {
         assert_eq!(data_vec, data_exp);
     }
 }
+
+#[test]
+fn test_reference_entry_old_format() {
+    let old = r#"{"id": "https://example.com", "author": "Wendt et al.", "year": 1999}"#;
+    let entry: ReferenceEntry = serde_json::from_str(old).unwrap();
+    assert_eq!(entry.title, None);
+    assert_eq!(entry.kind, ReferenceKind::Article);
+    assert_eq!(entry.short_citation(), "Wendt et al. (1999)");
+
+    // Unset details are not written out, so old readers see the same three fields.
+    let json = serde_json::to_value(ReferenceEntry::new_from_doi("10.500/1")).unwrap();
+    assert!(json.get("title").is_none());
+    assert!(json.get("kind").is_none());
+    assert_eq!(json.as_object().unwrap().len(), 3);
+}
+
+#[test]
+fn test_reference_citations() {
+    let entry = ReferenceEntry::new_from_url("https://example.com", "Rothe et al.", 2013)
+        .with_details(
+            "Measurement of the first ionization potential of astatine",
+            "Nat. Commun.",
+            "4",
+            "1835",
+            ReferenceKind::Article,
+        );
+    assert_eq!(
+        entry.full_citation(),
+        "Rothe et al. (2013). Measurement of the first ionization potential of astatine. \
+Nat. Commun. 4, 1835. https://example.com"
+    );
+
+    let thesis = ReferenceEntry::new_from_url("https://example.com/t", "Doe", 2020).with_details(
+        "",
+        "Mainz University",
+        "",
+        "",
+        ReferenceKind::Thesis,
+    );
+    assert_eq!(
+        thesis.markdown_citation(),
+        "- Doe (2020). Thesis, Mainz University. <https://example.com/t>"
+    );
+
+    let doi = ReferenceEntry::new_from_doi("10.500/123456789");
+    assert_eq!(doi.short_citation(), "10.500/123456789");
+    assert_eq!(doi.full_citation(), "https://doi.org/10.500/123456789");
+}
+
+#[test]
+fn test_check_references() {
+    let mut refs = vec![ReferenceEntry::new_from_doi("10.500/abc")];
+    assert!(check_references(&refs).is_ok());
+    refs.push(ReferenceEntry::new_from_doi("10.500/abc"));
+    assert!(check_references(&refs).is_err());
+    let refs = vec![ReferenceEntry::new_from_doi("https://doi.org/10.500/ABC")];
+    assert!(check_references(&refs).is_err());
+}
+
+#[test]
+fn test_apply_doi_metadata() {
+    let mut entry = ReferenceEntry::new_from_doi("10.500/abc");
+    assert!(entry.needs_doi_metadata());
+
+    entry.title = Some("Entered title".to_string());
+    entry.apply_doi_metadata(&DoiMetadata {
+        author: "Rothe et al.".to_string(),
+        year: 2013,
+        title: Some("Resolved title".to_string()),
+        journal: Some("Nat. Commun.".to_string()),
+        volume: None,
+        pages: None,
+    });
+    assert!(!entry.needs_doi_metadata());
+    assert_eq!(entry.title.as_deref(), Some("Entered title"));
+    assert_eq!(entry.journal.as_deref(), Some("Nat. Commun."));
+    assert_eq!(entry.get_url(), "https://doi.org/10.500/abc");
+
+    // resolved metadata does not turn the reference into a URL reference on export and reload
+    let mut app_entries = TemplateApp::default();
+    app_entries.submitted_by = "Jane Doe".to_string();
+    app_entries.document.scheme_transitions[0].level = "25000".to_string();
+    app_entries.document.references.push(entry);
+    let json = create_json_output(&app_entries).unwrap();
+    let exported: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(exported["references"][0]["author"], "");
+    assert_eq!(exported["references"][0]["year"], 0);
+    let (loaded, _) = load_submission(&json).unwrap();
+    assert!(loaded.document.references[0].needs_doi_metadata());
+    assert_eq!(
+        loaded.document.references[0].get_url(),
+        "https://doi.org/10.500/abc"
+    );
+    assert_eq!(
+        loaded.document.references[0].title.as_deref(),
+        Some("Entered title")
+    );
+}
+
+#[test]
+fn test_load_lenient_numbers() {
+    let (app_entries, report) = load_submission(
+        r#"{"scheme": {"element": "Ti", "lasers": "Ti:Sa", "unit": "nm",
+        "step_level0": 393.8, "step_term0": "3G3", "trans_strength0": "1e7",
+        "step_level1": " 300.5 ", "step_forbidden1": "true", "step_lowlying1": [],
+        "step_term2": "3D1"}}"#,
+    )
+    .unwrap();
+    assert_eq!(app_entries.document.scheme_gs.level, "0");
+    assert_eq!(app_entries.document.scheme_transitions[0].level, "393.8");
+    assert_eq!(
+        app_entries.document.scheme_transitions[0].transition_strength,
+        "1e7"
+    );
+    assert_eq!(app_entries.document.scheme_transitions[1].level, "300.5");
+    assert!(app_entries.document.scheme_transitions[1].forbidden);
+    assert_eq!(
+        report.fields,
+        vec![
+            "gs_level: missing, set to 0.",
+            "step_level0: converted number 393.8 to text.",
+            "step_lowlying1: ignored invalid value [].",
+            "step_forbidden1: converted text \"true\" to a boolean.",
+            "step_term2: ignored, since step_level2 is missing.",
+        ]
+    );
+}
+
+#[test]
+fn test_load_normalizes_references() {
+    // reference ids are normalized on load, independent of the format version
+    let (app_entries, report) = load_submission(&format!(
+        r#"{{"format_version": {}, "rims_scheme": {{"scheme": {{"element": "Ti", "lasers": "Ti:Sa", "unit": "nm"}}}},
+        "references": [
+            {{"id": "https://doi.org/10.500/ABC", "author": "", "year": 0}},
+            {{"id": "doi:10.500/abc", "author": "", "year": 0}}
+        ]}}"#,
+        FORMAT_VERSION
+    ))
+    .unwrap();
+    assert!(report.migrations.is_empty());
+    assert_eq!(app_entries.document.references.len(), 1);
+    assert_eq!(app_entries.document.references[0].id, "10.500/abc");
+}
+
+#[test]
+fn test_load_invalid_reference_kind() {
+    let (app_entries, report) = load_submission(
+        r#"{"scheme": {"element": "Ti", "lasers": "Ti:Sa", "unit": "nm", "gs_level": "0",
+        "step_level0": "393.8"},
+        "references": [{"id": "10.500/abc", "author": "", "year": 0, "kind": "Book"}]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        app_entries.document.references[0].kind,
+        ReferenceKind::Article
+    );
+    assert_eq!(
+        report.fields,
+        vec!["references/10.500/abc/kind: ignored invalid value \"Book\"."]
+    );
+}
+
+#[test]
+fn test_keep_unknown_keys() {
+    let config: Value = serde_json::from_str(
+        r#"{"scheme": {"element": "Ti", "lasers": "Ti:Sa", "unit": "nm", "gs_level": "0",
+        "step_level0": "393.8", "step_level7": "300.0", "comment": "test"},
+        "settings": {"fig_width": 5.0, "line_breakup": true}, "drawer_version": "2.1"}"#,
+    )
+    .unwrap();
+    let (mut app_entries, report) = load_submission(&config.to_string()).unwrap();
+    assert_eq!(report.fields.len(), 3);
+
+    let drawer: Value = serde_json::from_str(&create_drawer_output(&app_entries).unwrap()).unwrap();
+    assert_eq!(drawer["settings"], config["settings"]);
+    assert_eq!(drawer["drawer_version"], "2.1");
+    assert_eq!(drawer["scheme"]["comment"], "test");
+    assert_eq!(drawer["scheme"]["step_level7"], "300.0");
+
+    app_entries.submitted_by = "Jane Doe".to_string();
+    let database: Value = serde_json::from_str(&create_json_output(&app_entries).unwrap()).unwrap();
+    assert_eq!(database["rims_scheme"]["settings"], config["settings"]);
+
+    // a reload of the database entry keeps everything as well
+    let (app_entries, _) = load_submission(&database.to_string()).unwrap();
+    assert_eq!(
+        app_entries.document.drawer_settings,
+        Some(database["rims_scheme"]["settings"].clone())
+    );
+    assert_eq!(app_entries.document.extra_keys["drawer_version"], "2.1");
+}
+
+#[test]
+fn test_strength_input_export() {
+    let (app_entries, report) = load_submission(
+        r#"{"scheme": {"element": "Ti", "lasers": "Ti:Sa", "unit": "nm", "gs_level": "0",
+        "step_level0": "393.8", "trans_strength0": "4.5e7",
+        "trans_strength_input0": {"quantity": "log_gf", "value": -0.5, "wavelength": 393.8,
+        "j_lower": 2, "j_upper": 3}, "step_level1": "500", "trans_strength_input1": "gf"}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        report.fields,
+        vec!["trans_strength_input1: ignored invalid value \"gf\".".to_string()]
+    );
+    let input = app_entries.document.scheme_transitions[0]
+        .strength_input
+        .as_ref();
+    assert_eq!(input.map(|i| i.j_upper), Some(Some(3.0)));
+
+    let drawer: Value = serde_json::from_str(&create_drawer_output(&app_entries).unwrap()).unwrap();
+    assert_eq!(
+        drawer["scheme"]["trans_strength_input0"]["quantity"],
+        "log_gf"
+    );
+    assert!(drawer["scheme"]["trans_strength_input1"].is_null());
+}
+
+#[test]
+fn test_step_source_export() {
+    let (mut app_entries, report) = load_submission(
+        r#"{"scheme": {"element": "Ti", "lasers": "Ti:Sa", "unit": "nm", "gs_level": "0",
+        "step_level0": "393.8", "step_source0": "Literature", "step_reference0": "10.1000/abc",
+        "step_level1": "500.0", "step_source1": "guessed", "step_note1": "Remeasured"}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        report.fields,
+        vec!["step_source1: ignored invalid value \"guessed\".".to_string()]
+    );
+    assert_eq!(
+        app_entries.document.scheme_transitions[0].source,
+        Some(StepSource::Literature)
+    );
+
+    app_entries.document.scheme_transitions[1].source = Some(StepSource::ThisWork);
+    let scheme = create_scheme_json(&app_entries).unwrap();
+    assert_eq!(scheme["step_reference0"], "10.1000/abc");
+    assert_eq!(scheme["step_source1"], "ThisWork");
+    assert_eq!(scheme["step_note1"], "Remeasured");
+    assert!(scheme["step_note0"].is_null());
+}
+
+#[test]
+fn test_split_concise() {
+    let split = split_concise;
+    assert_eq!(
+        split("25123.45(3)"),
+        Some(("25123.45".to_string(), "0.03".to_string()))
+    );
+    assert_eq!(
+        split("25123.4(12)"),
+        Some(("25123.4".to_string(), "1.2".to_string()))
+    );
+    assert_eq!(split("394(2)"), Some(("394".to_string(), "2".to_string())));
+    assert_eq!(
+        split("1.23(4)e7"),
+        Some(("1.23e7".to_string(), "4e5".to_string()))
+    );
+    assert_eq!(
+        split("25123.4(1.2)"),
+        Some(("25123.4".to_string(), "1.2".to_string()))
+    );
+    assert_eq!(split("25123.45"), None);
+    assert_eq!(split("abc(3)"), None);
+}
+
+#[test]
+fn test_uncertainty_export() {
+    let (mut app_entries, report) = load_submission(
+        r#"{"scheme": {"element": "Ti", "lasers": "Ti:Sa", "unit": "cm<sup>-1</sup>",
+        "gs_level": "0", "gs_level_unc": "0.001", "step_level0": "25388.334",
+        "step_level_unc0": "0.005", "trans_strength0": "1e7", "trans_strength_unc0": 2e6}}"#,
+    )
+    .unwrap();
+    assert_eq!(app_entries.document.scheme_gs.level_unc, "0.001");
+    assert_eq!(
+        app_entries.document.scheme_transitions[0].level_unc,
+        "0.005"
+    );
+    assert_eq!(
+        app_entries.document.scheme_transitions[0].strength_unc,
+        "2000000.0"
+    );
+    assert_eq!(report.fields.len(), 1);
+
+    let scheme = create_scheme_json(&app_entries).unwrap();
+    assert_eq!(scheme["gs_level_unc"], "0.001");
+    assert_eq!(scheme["step_level_unc0"], "0.005");
+    assert!(scheme["step_level_unc1"].is_null());
+
+    app_entries.document.scheme_transitions[0].level_unc = "-1".to_string();
+    assert!(create_scheme_json(&app_entries).is_err());
+}
+
+#[test]
+fn test_drawer_output_default_settings() {
+    let (app_entries, _) = load_submission(
+        r#"{"scheme": {"element": "Ti", "lasers": "Ti:Sa", "unit": "nm", "gs_level": "0",
+        "step_level0": "393.8"}}"#,
+    )
+    .unwrap();
+    let drawer: Value = serde_json::from_str(&create_drawer_output(&app_entries).unwrap()).unwrap();
+    assert_eq!(drawer["settings"], default_drawer_settings());
+    assert_eq!(drawer["scheme"]["step_level0"], "393.8");
+}
+
+#[test]
+fn test_insert_transition() {
+    let mut transitions = TemplateApp::default().document.scheme_transitions;
+    transitions[0].level = "1".to_string();
+    transitions[1].level = "2".to_string();
+    insert_transition(&mut transitions, 1).unwrap();
+    assert_eq!(transitions[0].level, "1");
+    assert_eq!(transitions[1].level, "");
+    assert_eq!(transitions[2].level, "2");
+
+    transitions[6].level = "7".to_string();
+    assert!(insert_transition(&mut transitions, 0).is_err());
+}
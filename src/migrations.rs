@@ -0,0 +1,227 @@
+//! Versioning of the submission format and migrations of older files to the current model.
+//!
+//! Files without a `format_version` key are either plain RIMSSchemeDrawer configurations or
+//! database entries from before the format was versioned (version 0). RIMSSchemeDrawer
+//! configurations are first wrapped into the database layout, then all migrations from the file's
+//! version up to [`FORMAT_VERSION`] are applied in order.
+
+use serde::de::{Deserialize, Deserializer, IgnoredAny};
+use serde_json::{json, Value};
+
+use crate::documents::SchemeDocument;
+use crate::{ReferenceId, SubmissionFormat, TemplateApp};
+
+/// Current version of the submission format, written to every export.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// Upgrade of a database entry from one version to the next.
+struct Migration {
+    from: u64,
+    description: &'static str,
+    /// Apply the upgrade, returns if anything was changed.
+    apply: fn(&mut Value) -> Result<bool, String>,
+}
+
+const MIGRATIONS: [Migration; 1] = [Migration {
+    from: 0,
+    description: "Normalized reference ids and removed duplicate references.",
+    apply: normalize_references,
+}];
+
+/// Upgrade a loaded JSON file to the current database layout.
+///
+/// Returns the version of the file as it was loaded and the descriptions of all applied
+/// migrations.
+pub fn migrate(json: &mut Value) -> Result<(u64, Vec<String>), String> {
+    let mut applied = Vec::new();
+
+    let version = match SubmissionFormat::detect(json) {
+        Some(SubmissionFormat::Database) => match &json["format_version"] {
+            Value::Null => 0,
+            v => v
+                .as_u64()
+                .ok_or("The format version must be a positive integer.".to_string())?,
+        },
+        Some(SubmissionFormat::Drawer) => {
+            applied.extend(wrap_drawer_config(json));
+            0
+        }
+        None => return Err("No 'rims_scheme' or 'scheme' key found in the JSON file.".to_string()),
+    };
+
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "The file has format version {}, but only versions up to {} are supported. \
+Please use a newer version of this app.",
+            version, FORMAT_VERSION
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+        if !(migration.apply)(json)? {
+            continue;
+        }
+        applied.push(format!(
+            "Version {} to {}: {}",
+            migration.from,
+            migration.from + 1,
+            migration.description
+        ));
+    }
+    json["format_version"] = Value::from(FORMAT_VERSION);
+
+    Ok((version, applied))
+}
+
+/// Layout of the persisted app state, read from the same state as [`TemplateApp`].
+#[derive(serde::Deserialize)]
+pub struct PersistedLayout {
+    /// If the state has a `document` key. Older versions held the active document in top-level
+    /// keys, which are named like the fields of [`SchemeDocument`].
+    #[serde(default, rename = "document", deserialize_with = "key_present")]
+    pub has_document: bool,
+}
+
+fn key_present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    IgnoredAny::deserialize(deserializer).map(|_| true)
+}
+
+/// Upgrade the app state that was persisted by an older version of the app. `flat_document` is
+/// the active document read from the top-level keys of a state without a `document` key.
+pub fn migrate_persisted(app_entries: &mut TemplateApp, flat_document: Option<SchemeDocument>) {
+    if let Some(document) = flat_document {
+        app_entries.document = document;
+    }
+    if app_entries.format_version < 1 {
+        let mut json = json!({ "references": app_entries.document.references });
+        if normalize_references(&mut json).is_ok() {
+            if let Ok(refs) = serde_json::from_value(json["references"].take()) {
+                app_entries.document.references = refs;
+            }
+        }
+    }
+    app_entries.format_version = FORMAT_VERSION;
+}
+
+/// Move a RIMSSchemeDrawer configuration into the database layout and fill in keys that older
+/// versions of RIMSSchemeDrawer did not write.
+fn wrap_drawer_config(json: &mut Value) -> Vec<String> {
+    let mut applied =
+        vec!["Moved RIMSSchemeDrawer configuration into the database layout.".to_string()];
+
+    let mut rims_scheme = json!({});
+    if let Some(obj) = json.as_object_mut() {
+        for key in ["scheme", "settings"] {
+            if let Some(val) = obj.remove(key) {
+                rims_scheme[key] = val;
+            }
+        }
+    }
+
+    let scheme = &mut rims_scheme["scheme"];
+    if scheme["lasers"].is_null() {
+        scheme["lasers"] = Value::from("Ti:Sa");
+        applied
+            .push("Added laser type 'Ti:Sa', which older RIMSSchemeDrawer files lack.".to_string());
+    }
+    if scheme["last_step_to_ip"].is_null() {
+        scheme["last_step_to_ip"] = Value::from(false);
+    }
+
+    json["rims_scheme"] = rims_scheme;
+    applied
+}
+
+/// Version 0 to 1: Store all reference ids in normalized form, such that the same paper cannot be
+/// listed under two ids. Ids that cannot be normalized are kept and reported on export.
+///
+/// Files of the current version are normalized on load as well, this migration only reports it.
+fn normalize_references(json: &mut Value) -> Result<bool, String> {
+    let refs = match json["references"].as_array_mut() {
+        Some(refs) => refs,
+        None => return Ok(false),
+    };
+
+    let before = refs.clone();
+    let mut seen: Vec<String> = Vec::new();
+    refs.retain_mut(|r| {
+        let rid = match r["id"].as_str() {
+            Some(rid) => rid,
+            None => return true,
+        };
+        let rid = match rid.parse::<ReferenceId>() {
+            Ok(id) => id.as_str().to_owned(),
+            Err(_) => rid.to_owned(),
+        };
+        if seen.contains(&rid) {
+            return false;
+        }
+        r["id"] = Value::from(rid.as_str());
+        seen.push(rid);
+        true
+    });
+    Ok(refs != &before)
+}
+
+#[cfg(test)]
+#[test]
+fn test_migrate_drawer_config() {
+    let mut json = json!({
+        "scheme": {"element": "Ti", "step_level0": "25388.334"},
+        "settings": {"fig_width": 5}
+    });
+    let (version, applied) = migrate(&mut json).unwrap();
+    assert_eq!(version, 0);
+    // no references to normalize
+    assert_eq!(applied.len(), 2);
+    assert_eq!(json["rims_scheme"]["scheme"]["lasers"], "Ti:Sa");
+    assert_eq!(json["rims_scheme"]["settings"]["fig_width"], 5);
+    assert_eq!(json["format_version"], FORMAT_VERSION);
+    assert!(json.get("scheme").is_none());
+}
+
+#[test]
+fn test_migrate_database_v0() {
+    let mut json = json!({
+        "rims_scheme": {"scheme": {"element": "Ti"}},
+        "references": [
+            {"id": "https://doi.org/10.500/ABC", "author": "", "year": 0},
+            {"id": "10.500/abc", "author": "", "year": 0},
+            {"id": "https://example.com", "author": "Doe", "year": 2000}
+        ]
+    });
+    let (version, applied) = migrate(&mut json).unwrap();
+    assert_eq!(version, 0);
+    assert_eq!(applied.len(), 1);
+    assert_eq!(json["references"].as_array().unwrap().len(), 2);
+    assert_eq!(json["references"][0]["id"], "10.500/abc");
+}
+
+#[test]
+fn test_migrate_current_and_future() {
+    let mut json = json!({"rims_scheme": {"scheme": {}}, "format_version": FORMAT_VERSION});
+    assert_eq!(migrate(&mut json).unwrap(), (FORMAT_VERSION, Vec::new()));
+
+    let mut json = json!({"rims_scheme": {"scheme": {}}, "format_version": FORMAT_VERSION + 1});
+    assert!(migrate(&mut json).is_err());
+}
+
+#[test]
+fn test_migrate_persisted() {
+    let state = r#"{"references": [
+        {"id": "https://doi.org/10.500/ABC", "author": "", "year": 0},
+        {"id": "10.500/abc", "author": "", "year": 0}
+    ]}"#;
+    let mut app_entries: TemplateApp = serde_json::from_str(state).unwrap();
+    assert_eq!(app_entries.format_version, 0);
+    let layout: PersistedLayout = serde_json::from_str(state).unwrap();
+    assert!(!layout.has_document);
+    migrate_persisted(&mut app_entries, serde_json::from_str(state).ok());
+    assert_eq!(app_entries.format_version, FORMAT_VERSION);
+    assert_eq!(app_entries.document.references.len(), 1);
+
+    // the current layout holds the active document in its own key
+    let state = serde_json::to_string(&app_entries).unwrap();
+    let layout: PersistedLayout = serde_json::from_str(&state).unwrap();
+    assert!(layout.has_document);
+}
@@ -0,0 +1,104 @@
+//! Thermal populations of the ground state and the low-lying states of a scheme.
+//!
+//! The populations follow a Boltzmann distribution at the atomizer temperature. The statistical
+//! weights `g = 2J + 1` are taken from the term symbols. If a level table of the element is loaded,
+//! the partition function is calculated from all its levels, otherwise only from the states of the
+//! scheme.
+
+use crate::diagram::ground_state_energy;
+use crate::levels::LevelTable;
+use crate::strength::j_from_term;
+use crate::TemplateApp;
+
+/// Boltzmann constant in cm⁻¹/K.
+const BOLTZMANN: f64 = 0.695_034_800;
+/// Default temperature of the atomizer in K.
+pub const DEFAULT_TEMPERATURE: f64 = 2000.0;
+
+/// Thermal population of a starting state of the scheme.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatePopulation {
+    /// Index of the low-lying step, `None` for the ground state.
+    pub step: Option<usize>,
+    /// Fraction of all atoms in this state, `None` if J is not given in the term symbol.
+    pub fraction: Option<f64>,
+}
+
+/// Populations of the ground state and all low-lying states at `temperature` in K.
+pub fn thermal_populations(
+    app_entries: &TemplateApp,
+    temperature: f64,
+    table: Option<&LevelTable>,
+) -> Result<Vec<StatePopulation>, String> {
+    if temperature <= 0.0 {
+        return Err("The temperature must be positive.".to_string());
+    }
+    let ground = ground_state_energy(app_entries)?;
+    let weight = |j: f64, energy: f64| {
+        (2.0 * j + 1.0) * (-(energy - ground) / (BOLTZMANN * temperature)).exp()
+    };
+
+    let mut states = vec![(
+        None,
+        ground,
+        j_from_term(&app_entries.document.scheme_gs.term_symbol),
+    )];
+    for (it, trans) in app_entries.document.scheme_transitions.iter().enumerate() {
+        if !trans.low_lying || trans.level.is_empty() {
+            continue;
+        }
+        let energy = trans
+            .level
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("Step {}: Level is not a number.", it + 1))?;
+        states.push((Some(it), energy, j_from_term(&trans.term_symbol)));
+    }
+
+    let partition_function: f64 = match table {
+        Some(table) => table
+            .levels
+            .iter()
+            .filter_map(|lvl| Some(weight(j_from_term(&lvl.j)?, lvl.energy)))
+            .sum(),
+        None => states
+            .iter()
+            .filter_map(|(_, energy, j)| Some(weight((*j)?, *energy)))
+            .sum(),
+    };
+
+    Ok(states
+        .into_iter()
+        .map(|(step, energy, j)| StatePopulation {
+            step,
+            fraction: match partition_function > 0.0 {
+                true => j.map(|j| weight(j, energy) / partition_function),
+                false => None,
+            },
+        })
+        .collect())
+}
+
+#[cfg(test)]
+#[test]
+fn test_thermal_populations() {
+    let mut app_entries = TemplateApp::default();
+    app_entries.document.scheme_gs.term_symbol = "3F2".to_string();
+    app_entries.document.scheme_transitions[0].level = "170.1325".to_string();
+    app_entries.document.scheme_transitions[0].term_symbol = "3F3".to_string();
+    app_entries.document.scheme_transitions[0].low_lying = true;
+    app_entries.document.scheme_transitions[1].level = "25388.334".to_string();
+    app_entries.document.scheme_transitions[2].level = "386.8738".to_string();
+    app_entries.document.scheme_transitions[2].low_lying = true;
+
+    let populations = thermal_populations(&app_entries, 2000.0, None).unwrap();
+    assert_eq!(populations.len(), 3);
+    let ratio = 7.0 / 5.0 * (-170.1325 / (BOLTZMANN * 2000.0f64)).exp();
+    let ground = populations[0].fraction.unwrap();
+    assert!((ground - 1.0 / (1.0 + ratio)).abs() < 1e-12);
+    assert_eq!(populations[1].step, Some(0));
+    // no J given for the third state
+    assert_eq!(populations[2].fraction, None);
+
+    assert!(thermal_populations(&app_entries, 0.0, None).is_err());
+}
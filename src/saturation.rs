@@ -0,0 +1,496 @@
+//! Fit of saturation curves and the quantities derived from the fitted saturation, given the
+//! parameters of the laser.
+//!
+//! Saturation curves are described by `y = A * x / (x + x_sat)`, where `x` is the irradiance or
+//! the power of the laser and `x_sat` the saturation irradiance or power.
+
+use serde_json::{json, Value};
+
+use crate::diagram::{tick_values, Anchor, Color, Ink, Primitive, SchemeLayout};
+use crate::{SaturationCurve, SaturationCurveUnit};
+
+/// Planck constant times speed of light in J m.
+const HC: f64 = 1.986_445_857e-25;
+/// Number of iterations of the search for the saturation.
+const FIT_ITERATIONS: usize = 200;
+
+/// Colors of the curves in the comparison plot.
+pub const CURVE_COLORS: [Color; 6] = [
+    [31, 119, 180],
+    [255, 127, 14],
+    [44, 160, 44],
+    [214, 39, 40],
+    [148, 103, 189],
+    [140, 86, 75],
+];
+const PLOT_MARGIN_LEFT: f32 = 50.0;
+const PLOT_MARGIN_RIGHT: f32 = 20.0;
+const PLOT_MARGIN_TOP: f32 = 25.0;
+const PLOT_MARGIN_BOTTOM: f32 = 40.0;
+const PLOT_FONT_SIZE: f32 = 11.0;
+const PLOT_MARKER_SIZE: f32 = 3.0;
+const PLOT_FIT_SEGMENTS: usize = 80;
+/// Upper end of the normalized signal axis.
+const PLOT_Y_MAX: f64 = 1.15;
+
+/// Parameters of the laser used to measure a saturation curve. All of them are optional.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LaserParameters {
+    /// Repetition rate in kHz.
+    pub repetition_rate: Option<f64>,
+    /// Wavelength in nm.
+    pub wavelength: Option<f64>,
+    /// Diameter of the (top-hat) beam in mm.
+    pub beam_diameter: Option<f64>,
+}
+
+impl LaserParameters {
+    /// Read the parameters from the text fields of the form, empty fields are not set.
+    pub fn from_parts(
+        repetition_rate: &str,
+        wavelength: &str,
+        beam_diameter: &str,
+    ) -> Result<Self, String> {
+        let parse = |inp: &str, name: &str| match inp.trim() {
+            "" => Ok(None),
+            v => match v.parse::<f64>() {
+                Ok(v) if v > 0.0 => Ok(Some(v)),
+                _ => Err(format!("The {} must be a positive number.", name)),
+            },
+        };
+        Ok(Self {
+            repetition_rate: parse(repetition_rate, "repetition rate")?,
+            wavelength: parse(wavelength, "wavelength")?,
+            beam_diameter: parse(beam_diameter, "beam diameter")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Beam area in cm².
+    fn beam_area(&self) -> Option<f64> {
+        self.beam_diameter
+            .map(|d| std::f64::consts::PI * (d / 20.0).powi(2))
+    }
+
+    /// Entry of the exported saturation curve, unset parameters are left out.
+    pub fn to_json(&self) -> Value {
+        let mut entry = json!({});
+        for (key, value) in [
+            ("repetition_rate", self.repetition_rate),
+            ("wavelength", self.wavelength),
+            ("beam_diameter", self.beam_diameter),
+        ] {
+            if let Some(value) = value {
+                entry[key] = Value::from(value);
+            }
+        }
+        entry
+    }
+
+    pub fn from_json(entry: &Value) -> Self {
+        Self {
+            repetition_rate: entry["repetition_rate"].as_f64(),
+            wavelength: entry["wavelength"].as_f64(),
+            beam_diameter: entry["beam_diameter"].as_f64(),
+        }
+    }
+}
+
+/// Result of the fit of a saturation curve.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaturationFit {
+    pub amplitude: f64,
+    pub amplitude_unc: f64,
+    /// Saturation in the unit of the x-data.
+    pub saturation: f64,
+    pub saturation_unc: f64,
+}
+
+impl SaturationFit {
+    /// Value of the fitted curve at `x`.
+    pub fn eval(&self, x: f64) -> f64 {
+        self.amplitude * x / (x + self.saturation)
+    }
+}
+
+/// Fit the saturation curve by weighted least squares, using the y-uncertainties as weights if
+/// given. The amplitude is solved for analytically, the saturation is searched on a logarithmic
+/// scale. Uncertainties are taken from the covariance matrix, scaled by the reduced chi-square if
+/// no y-uncertainties are given.
+pub fn fit_saturation(curve: &SaturationCurve) -> Result<SaturationFit, String> {
+    let (x, y) = (&curve.xdat, &curve.ydat);
+    if x.len() < 3 {
+        return Err("At least three data points are required for a fit.".to_string());
+    }
+    if x.iter().any(|x| *x < 0.0) {
+        return Err("The x-data must not be negative for a fit.".to_string());
+    }
+    // weight by the y-uncertainties only if all of them are usable
+    let weighted = curve
+        .ydat_unc
+        .as_ref()
+        .is_some_and(|unc| unc.iter().all(|u| *u > 0.0));
+    let weights: Vec<f64> = match (&curve.ydat_unc, weighted) {
+        (Some(unc), true) => unc.iter().map(|u| 1.0 / (u * u)).collect(),
+        _ => vec![1.0; x.len()],
+    };
+
+    // best amplitude and chi-square for a given saturation
+    let solve = |sat: f64| -> (f64, f64) {
+        let f: Vec<f64> = x.iter().map(|x| x / (x + sat)).collect();
+        let sfy: f64 = (0..x.len()).map(|i| weights[i] * f[i] * y[i]).sum();
+        let sff: f64 = (0..x.len()).map(|i| weights[i] * f[i] * f[i]).sum();
+        let amp = if sff > 0.0 { sfy / sff } else { 0.0 };
+        let chi2 = (0..x.len())
+            .map(|i| weights[i] * (y[i] - amp * f[i]).powi(2))
+            .sum();
+        (amp, chi2)
+    };
+
+    // golden-section search of log(saturation) over a wide range around the data
+    let x_max = x.iter().cloned().fold(0.0, f64::max);
+    if x_max <= 0.0 {
+        return Err("The x-data must contain positive values for a fit.".to_string());
+    }
+    let (mut lo, mut hi) = ((x_max * 1e-4).ln(), (x_max * 1e4).ln());
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    for _ in 0..FIT_ITERATIONS {
+        let a = hi - ratio * (hi - lo);
+        let b = lo + ratio * (hi - lo);
+        match solve(a.exp()).1 < solve(b.exp()).1 {
+            true => hi = b,
+            false => lo = a,
+        }
+    }
+    let saturation = ((lo + hi) / 2.0).exp();
+    let (amplitude, chi2) = solve(saturation);
+
+    // covariance matrix from the Jacobian
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    for i in 0..x.len() {
+        let da = x[i] / (x[i] + saturation);
+        let db = -amplitude * x[i] / (x[i] + saturation).powi(2);
+        aa += weights[i] * da * da;
+        ab += weights[i] * da * db;
+        bb += weights[i] * db * db;
+    }
+    let det = aa * bb - ab * ab;
+    if det <= 0.0 || !amplitude.is_finite() {
+        return Err("The saturation curve cannot be fitted.".to_string());
+    }
+    let scale = match weighted {
+        true => 1.0,
+        false => chi2 / (x.len() - 2) as f64,
+    };
+    Ok(SaturationFit {
+        amplitude,
+        amplitude_unc: (scale * bb / det).sqrt(),
+        saturation,
+        saturation_unc: (scale * aa / det).sqrt(),
+    })
+}
+
+/// Quantities derived from the fitted saturation and the laser parameters, `None` if the
+/// required parameters are not given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DerivedQuantities {
+    /// Saturation fluence per pulse in J/cm².
+    pub saturation_fluence: Option<f64>,
+    /// Cross-section of the excitation or ionization in cm².
+    pub cross_section: Option<f64>,
+    /// Average laser power in W that is required for 90% saturation.
+    pub power_90: Option<f64>,
+}
+
+impl DerivedQuantities {
+    /// Derive the quantities for a saturation in the given unit.
+    ///
+    /// The saturation fluence is the saturation irradiance divided by the repetition rate, the
+    /// cross-section is the inverse of the saturation photon fluence. The curve reaches 90% of
+    /// its amplitude at nine times the saturation. Powers and irradiances are converted with the
+    /// beam area.
+    pub fn new(saturation: f64, unit: &SaturationCurveUnit, laser: &LaserParameters) -> Self {
+        let area = laser.beam_area();
+        let irradiance = match unit {
+            SaturationCurveUnit::WCM2 => Some(saturation),
+            SaturationCurveUnit::W => area.map(|a| saturation / a),
+        };
+        let saturation_fluence = match (irradiance, laser.repetition_rate) {
+            (Some(i), Some(rate)) => Some(i / (rate * 1e3)),
+            _ => None,
+        };
+        let cross_section = match (saturation_fluence, laser.wavelength) {
+            (Some(f), Some(wl)) => Some(HC / (wl * 1e-9) / f),
+            _ => None,
+        };
+        let power_90 = match unit {
+            SaturationCurveUnit::W => Some(9.0 * saturation),
+            SaturationCurveUnit::WCM2 => area.map(|a| 9.0 * saturation * a),
+        };
+        Self {
+            saturation_fluence,
+            cross_section,
+            power_90,
+        }
+    }
+
+    /// Entry of the exported saturation curve, with the fitted saturation.
+    pub fn to_json(&self, fit: &SaturationFit) -> Value {
+        let mut entry = json!({
+            "saturation": fit.saturation,
+            "saturation_err": fit.saturation_unc,
+        });
+        for (key, value) in [
+            ("saturation_fluence", self.saturation_fluence),
+            ("cross_section", self.cross_section),
+            ("power_90", self.power_90),
+        ] {
+            if let Some(value) = value {
+                entry[key] = Value::from(value);
+            }
+        }
+        entry
+    }
+}
+
+/// Overlay of saturation curves for comparison. The signals are normalized to the fitted
+/// amplitude or, if the curve cannot be fitted, to the largest signal. Fits are drawn as lines, the
+/// data as crosses with their uncertainties. Curves are colored by their position in `curves`.
+pub fn layout_comparison(curves: &[&SaturationCurve], width: f32, height: f32) -> SchemeLayout {
+    let mut prims = Vec::new();
+    let x_max = curves
+        .iter()
+        .flat_map(|c| c.xdat.iter())
+        .cloned()
+        .fold(0.0, f64::max);
+    let x_max = match x_max > 0.0 {
+        true => x_max * 1.05,
+        false => 1.0,
+    };
+    let (plot_w, plot_h) = (
+        width - PLOT_MARGIN_LEFT - PLOT_MARGIN_RIGHT,
+        height - PLOT_MARGIN_TOP - PLOT_MARGIN_BOTTOM,
+    );
+    let px = |x: f64| PLOT_MARGIN_LEFT + (x / x_max) as f32 * plot_w;
+    let py = |y: f64| PLOT_MARGIN_TOP + plot_h - (y / PLOT_Y_MAX) as f32 * plot_h;
+    let line = |from: [f32; 2], to: [f32; 2], ink: Ink| Primitive::Line {
+        from,
+        to,
+        ink,
+        width: 1.0,
+        dashed: false,
+    };
+    let text = |pos: [f32; 2], text: String, anchor: Anchor| Primitive::Text {
+        pos,
+        text,
+        size: PLOT_FONT_SIZE,
+        anchor,
+        ink: Ink::Foreground,
+    };
+
+    // axes with ticks
+    prims.push(line(
+        [px(0.0), py(0.0)],
+        [px(x_max), py(0.0)],
+        Ink::Foreground,
+    ));
+    prims.push(line(
+        [px(0.0), py(0.0)],
+        [px(0.0), py(PLOT_Y_MAX)],
+        Ink::Foreground,
+    ));
+    for tick in tick_values(0.0, x_max) {
+        prims.push(line(
+            [px(tick), py(0.0)],
+            [px(tick), py(0.0) + 4.0],
+            Ink::Foreground,
+        ));
+        prims.push(text(
+            [px(tick), py(0.0) + 12.0],
+            format_tick(tick),
+            Anchor::Middle,
+        ));
+    }
+    for tick in tick_values(0.0, PLOT_Y_MAX) {
+        prims.push(line(
+            [px(0.0) - 4.0, py(tick)],
+            [px(0.0), py(tick)],
+            Ink::Foreground,
+        ));
+        prims.push(text(
+            [px(0.0) - 6.0, py(tick)],
+            format_tick(tick),
+            Anchor::End,
+        ));
+    }
+    let units: Vec<String> = curves.iter().map(|c| c.units.to_string()).collect();
+    let x_label = match units.iter().all(|u| Some(u) == units.first()) {
+        true => match curves.first().map(|c| &c.units) {
+            Some(SaturationCurveUnit::W) => "Power (W)",
+            _ => "Irradiance (W/cm²)",
+        },
+        false => "Irradiance (W/cm²) or power (W)",
+    };
+    prims.push(text(
+        [PLOT_MARGIN_LEFT + plot_w / 2.0, height - 10.0],
+        x_label.to_string(),
+        Anchor::Middle,
+    ));
+    prims.push(text(
+        [PLOT_MARGIN_LEFT, PLOT_MARGIN_TOP / 2.0],
+        "Normalized signal".to_string(),
+        Anchor::Start,
+    ));
+
+    for (it, curve) in curves.iter().enumerate() {
+        let color = CURVE_COLORS[it % CURVE_COLORS.len()];
+        let fit = fit_saturation(curve).ok();
+        let norm = match &fit {
+            Some(fit) if fit.amplitude > 0.0 => fit.amplitude,
+            _ => curve.ydat.iter().cloned().fold(0.0, f64::max),
+        };
+        if norm <= 0.0 {
+            continue;
+        }
+
+        for (i, (x, y)) in curve.xdat.iter().zip(curve.ydat.iter()).enumerate() {
+            let (cx, cy) = (px(*x), py(y / norm));
+            prims.push(line(
+                [cx - PLOT_MARKER_SIZE, cy - PLOT_MARKER_SIZE],
+                [cx + PLOT_MARKER_SIZE, cy + PLOT_MARKER_SIZE],
+                Ink::Rgb(color),
+            ));
+            prims.push(line(
+                [cx - PLOT_MARKER_SIZE, cy + PLOT_MARKER_SIZE],
+                [cx + PLOT_MARKER_SIZE, cy - PLOT_MARKER_SIZE],
+                Ink::Rgb(color),
+            ));
+            if let Some(unc) = curve.ydat_unc.as_ref().and_then(|u| u.get(i)) {
+                prims.push(line(
+                    [cx, py((y - unc) / norm)],
+                    [cx, py((y + unc) / norm)],
+                    Ink::Rgb(color),
+                ));
+            }
+        }
+
+        if let Some(fit) = fit {
+            let points: Vec<[f32; 2]> = (0..=PLOT_FIT_SEGMENTS)
+                .map(|i| {
+                    let x = x_max * i as f64 / PLOT_FIT_SEGMENTS as f64;
+                    [px(x), py(fit.eval(x) / norm)]
+                })
+                .collect();
+            for segment in points.windows(2) {
+                prims.push(Primitive::Line {
+                    from: segment[0],
+                    to: segment[1],
+                    ink: Ink::Rgb(color),
+                    width: 1.5,
+                    dashed: false,
+                });
+            }
+        }
+    }
+
+    SchemeLayout {
+        width,
+        height,
+        primitives: prims,
+        targets: Vec::new(),
+    }
+}
+
+/// Tick label without rounding artifacts, e.g., "0.2" instead of "0.20000000000000001".
+fn format_tick(tick: f64) -> String {
+    let text = format!("{:.6}", tick);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+fn test_curve(sat: f64, unit: SaturationCurveUnit) -> SaturationCurve {
+    let xdat: Vec<f64> = (1..=10).map(|x| x as f64 * 0.5).collect();
+    let ydat = xdat.iter().map(|x| 100.0 * x / (x + sat)).collect();
+    SaturationCurve {
+        title: "Step 1".to_string(),
+        notes: String::new(),
+        units: unit,
+        fit: true,
+        xdat,
+        xdat_unc: None,
+        ydat,
+        ydat_unc: None,
+        laser: LaserParameters::default(),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_fit_saturation() {
+    let curve = test_curve(1.2, SaturationCurveUnit::WCM2);
+    let fit = fit_saturation(&curve).unwrap();
+    assert!((fit.saturation - 1.2).abs() < 1e-6, "{:?}", fit);
+    assert!((fit.amplitude - 100.0).abs() < 1e-4, "{:?}", fit);
+    assert!((fit.eval(9.0 * fit.saturation) / fit.amplitude - 0.9).abs() < 1e-12);
+
+    let mut short = curve.clone();
+    short.xdat.truncate(2);
+    short.ydat.truncate(2);
+    assert!(fit_saturation(&short).is_err());
+
+    // a zero uncertainty falls back to the unweighted fit, including its uncertainties
+    let mut noisy = curve.clone();
+    for (it, y) in noisy.ydat.iter_mut().enumerate() {
+        *y += if it % 2 == 0 { 1.0 } else { -1.0 };
+    }
+    let unweighted = fit_saturation(&noisy).unwrap();
+    let mut unc = vec![1.0; noisy.ydat.len()];
+    unc[0] = 0.0;
+    noisy.ydat_unc = Some(unc);
+    assert_eq!(fit_saturation(&noisy).unwrap(), unweighted);
+}
+
+#[test]
+fn test_derived_quantities() {
+    let laser = LaserParameters::from_parts("10", "400", "2").unwrap();
+    let derived = DerivedQuantities::new(1.0, &SaturationCurveUnit::WCM2, &laser);
+    // 1 W/cm² at 10 kHz are 100 µJ/cm² per pulse
+    assert!((derived.saturation_fluence.unwrap() - 1e-4).abs() < 1e-12);
+    let photon_energy = HC / 400e-9;
+    assert!((derived.cross_section.unwrap() - photon_energy / 1e-4).abs() < 1e-24);
+    // 9 W/cm² on a beam of 2 mm diameter
+    let area = std::f64::consts::PI * 0.01;
+    assert!((derived.power_90.unwrap() - 9.0 * area).abs() < 1e-12);
+
+    // powers need the beam size for the fluence
+    let derived = DerivedQuantities::new(0.5, &SaturationCurveUnit::W, &LaserParameters::default());
+    assert_eq!(derived.saturation_fluence, None);
+    assert_eq!(derived.power_90, Some(4.5));
+
+    assert!(LaserParameters::from_parts("-1", "", "").is_err());
+}
+
+#[test]
+fn test_layout_comparison() {
+    let first = test_curve(1.2, SaturationCurveUnit::WCM2);
+    let second = test_curve(0.5, SaturationCurveUnit::WCM2);
+    let layout = layout_comparison(&[&first, &second], 400.0, 300.0);
+    // both fits end close to the top of the normalized axis
+    let fit_ends: Vec<f32> = layout
+        .primitives
+        .iter()
+        .filter_map(|p| match p {
+            Primitive::Line { width, to, .. } if *width == 1.5 && to[0] == 380.0 => Some(to[1]),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(fit_ends.len(), 2);
+    assert!(fit_ends.iter().all(|y| *y > 25.0 && *y < 100.0));
+    assert!(layout.primitives.iter().any(|p| matches!(
+        p,
+        Primitive::Text { text, .. } if text == "Irradiance (W/cm²)"
+    )));
+}
@@ -0,0 +1,400 @@
+//! JSON Schema (draft 2020-12) of the database submission format.
+//!
+//! The schema is generated from the Rust model, e.g., the list of elements and the number of
+//! steps, such that it cannot get out of sync with what the app writes. A small validator for the
+//! keywords used in the schema allows checking files with precise error paths.
+
+use std::fmt;
+
+use serde_json::{json, Map, Value};
+use strum::IntoEnumIterator;
+
+use crate::strength::StrengthQuantity;
+use crate::{Elements, Lasers, ReferenceKind, StepSource, TemplateApp, FORMAT_VERSION};
+
+/// Pattern for a level or transition strength, which are stored as strings.
+const NUMBER_PATTERN: &str = r"^[-+]?([0-9]+\.?[0-9]*|\.[0-9]+)([eE][-+]?[0-9]+)?$";
+
+/// A single violation of the schema.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SchemaError {
+    /// JSON pointer to the offending value, e.g., `/rims_scheme/scheme/step_level0`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.path.is_empty() {
+            true => write!(f, "(root): {}", self.message),
+            false => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// Generate the JSON Schema of a database submission in the current format version.
+pub fn submission_schema() -> Value {
+    let elements: Vec<String> = Elements::iter().map(|e| format!("{:?}", e)).collect();
+    let lasers: Vec<String> = [Lasers::TiSa, Lasers::Dye, Lasers::Both]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+    let kinds: Vec<String> = ReferenceKind::iter().map(|k| format!("{:?}", k)).collect();
+    let sources: Vec<String> = StepSource::iter().map(|s| format!("{:?}", s)).collect();
+    let quantities: Vec<&str> = StrengthQuantity::iter().map(|q| q.key()).collect();
+    let last_step = TemplateApp::default().document.scheme_transitions.len() - 1;
+
+    let mut step_properties = Map::new();
+    for (key, def) in [
+        ("step_level", json!({"$ref": "#/$defs/number_string"})),
+        (
+            "step_level_unc",
+            json!({"$ref": "#/$defs/optional_number_string"}),
+        ),
+        ("step_term", json!({"type": "string"})),
+        (
+            "trans_strength",
+            json!({"$ref": "#/$defs/optional_number_string"}),
+        ),
+        (
+            "trans_strength_unc",
+            json!({"$ref": "#/$defs/optional_number_string"}),
+        ),
+        ("step_source", json!({"enum": sources})),
+        ("step_reference", json!({"type": "string"})),
+        ("step_note", json!({"type": "string"})),
+        ("step_forbidden", json!({"type": "boolean"})),
+        ("step_lowlying", json!({"type": "boolean"})),
+        (
+            "trans_strength_input",
+            json!({"$ref": "#/$defs/strength_input"}),
+        ),
+    ] {
+        step_properties.insert(format!("^{}[0-{}]$", key, last_step), def);
+    }
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "RIMS scheme database submission",
+        "description": format!("Resonance ionization scheme, format version {}.", FORMAT_VERSION),
+        "type": "object",
+        "required": ["format_version", "notes", "rims_scheme", "references", "saturation_curves", "submitted_by"],
+        "properties": {
+            "format_version": {"const": FORMAT_VERSION},
+            "notes": {"type": "string"},
+            "rims_scheme": {
+                "type": "object",
+                "required": ["scheme"],
+                "properties": {
+                    "scheme": {"$ref": "#/$defs/scheme"},
+                    "settings": {"type": "object"},
+                },
+            },
+            "references": {"type": "array", "items": {"$ref": "#/$defs/reference"}},
+            "saturation_curves": {"type": "array", "items": {"$ref": "#/$defs/saturation_curve"}},
+            "submitted_by": {"type": "string", "minLength": 1},
+        },
+        "$defs": {
+            "number_string": {
+                "type": "string",
+                "description": "A number written as text.",
+                "pattern": NUMBER_PATTERN,
+            },
+            "optional_number_string": {
+                "type": "string",
+                "description": "A number written as text, or empty.",
+                "pattern": format!("^$|{}", NUMBER_PATTERN),
+            },
+            "number_array": {"type": "array", "items": {"type": "number"}},
+            "scheme": {
+                "type": "object",
+                "required": ["element", "lasers", "last_step_to_ip", "gs_term", "gs_level", "ip_term", "unit", "step_level0"],
+                "properties": {
+                    "element": {"enum": elements},
+                    "lasers": {"enum": lasers},
+                    "last_step_to_ip": {"type": "boolean"},
+                    "gs_term": {"type": "string"},
+                    "gs_level": {"$ref": "#/$defs/number_string"},
+                    "gs_level_unc": {"$ref": "#/$defs/optional_number_string"},
+                    "ip_term": {"type": "string"},
+                    "unit": {"enum": ["nm", "cm<sup>-1</sup>"]},
+                },
+                "patternProperties": step_properties,
+            },
+            "strength_input": {
+                "type": "object",
+                "required": ["quantity", "value"],
+                "properties": {
+                    "quantity": {"enum": quantities},
+                    "value": {"type": "number"},
+                    "wavelength": {"type": "number", "minimum": 0},
+                    "j_lower": {"type": "number", "minimum": 0},
+                    "j_upper": {"type": "number", "minimum": 0},
+                    "branching_ratio": {"type": "number", "minimum": 0},
+                },
+            },
+            "reference": {
+                "type": "object",
+                "required": ["id", "author", "year"],
+                "properties": {
+                    "id": {"type": "string", "minLength": 1},
+                    "author": {"type": "string"},
+                    "year": {"type": "integer", "minimum": 0},
+                    "title": {"type": "string"},
+                    "journal": {"type": "string"},
+                    "volume": {"type": "string"},
+                    "pages": {"type": "string"},
+                    "kind": {"enum": kinds},
+                },
+            },
+            "saturation_curve": {
+                "type": "object",
+                "required": ["title", "notes", "unit", "fit", "data"],
+                "properties": {
+                    "title": {"type": "string", "minLength": 1},
+                    "notes": {"type": "string"},
+                    "unit": {"enum": ["W * cm^-2", "W"]},
+                    "fit": {"type": "boolean"},
+                    "laser": {
+                        "type": "object",
+                        "properties": {
+                            "repetition_rate": {"type": "number", "minimum": 0},
+                            "wavelength": {"type": "number", "minimum": 0},
+                            "beam_diameter": {"type": "number", "minimum": 0},
+                        },
+                    },
+                    "derived": {
+                        "type": "object",
+                        "required": ["saturation"],
+                        "properties": {
+                            "saturation": {"type": "number"},
+                            "saturation_err": {"type": "number"},
+                            "saturation_fluence": {"type": "number"},
+                            "cross_section": {"type": "number"},
+                            "power_90": {"type": "number"},
+                        },
+                    },
+                    "data": {
+                        "type": "object",
+                        "required": ["x", "y"],
+                        "properties": {
+                            "x": {"$ref": "#/$defs/number_array"},
+                            "y": {"$ref": "#/$defs/number_array"},
+                            "x_err": {"$ref": "#/$defs/number_array"},
+                            "y_err": {"$ref": "#/$defs/number_array"},
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+/// Validate a database submission against the generated schema.
+pub fn validate_submission_json(instance: &Value) -> Vec<SchemaError> {
+    let schema = submission_schema();
+    let mut errors = Vec::new();
+    validate_node(instance, &schema, &schema, "", &mut errors);
+    errors
+}
+
+/// Validate a value against a (sub-)schema. Only the keywords used in the generated schema are
+/// supported.
+fn validate_node(
+    instance: &Value,
+    schema: &Value,
+    root: &Value,
+    path: &str,
+    errors: &mut Vec<SchemaError>,
+) {
+    let mut error = |message: String| {
+        errors.push(SchemaError {
+            path: path.to_string(),
+            message,
+        })
+    };
+
+    if let Some(reference) = schema["$ref"].as_str() {
+        match reference.strip_prefix('#').and_then(|p| root.pointer(p)) {
+            Some(sub) => validate_node(instance, sub, root, path, errors),
+            None => error(format!("Cannot resolve schema reference {}.", reference)),
+        }
+        return;
+    }
+
+    if let Some(expected) = schema["type"].as_str() {
+        if !has_type(instance, expected) {
+            error(format!(
+                "Expected {}, found {}.",
+                expected,
+                type_name(instance)
+            ));
+            return;
+        }
+    }
+    if !schema["const"].is_null() && instance != &schema["const"] {
+        error(format!("Expected {}, found {}.", schema["const"], instance));
+    }
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(instance) {
+            let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+            error(format!(
+                "Value {} is not one of {}.",
+                instance,
+                options.join(", ")
+            ));
+        }
+    }
+
+    if let Some(s) = instance.as_str() {
+        if let Some(min) = schema["minLength"].as_u64() {
+            if (s.chars().count() as u64) < min {
+                error(format!("String must have at least {} character(s).", min));
+            }
+        }
+        if let Some(pattern) = schema["pattern"].as_str() {
+            if let Ok(re) = regex::Regex::new(pattern) {
+                if !re.is_match(s) {
+                    error(match schema["description"].as_str() {
+                        Some(description) => format!("\"{}\" does not match: {}", s, description),
+                        None => format!("\"{}\" does not match the pattern {}.", s, pattern),
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(n) = instance.as_f64() {
+        if let Some(min) = schema["minimum"].as_f64() {
+            if n < min {
+                error(format!("Value {} is smaller than the minimum {}.", n, min));
+            }
+        }
+    }
+
+    if let Some(obj) = instance.as_object() {
+        if let Some(required) = schema["required"].as_array() {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !obj.contains_key(key) {
+                    error(format!("Required key '{}' is missing.", key));
+                }
+            }
+        }
+        for (key, val) in obj {
+            let sub_path = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+            if let Some(sub) = schema["properties"].get(key) {
+                validate_node(val, sub, root, &sub_path, errors);
+            }
+            if let Some(patterns) = schema["patternProperties"].as_object() {
+                for (pattern, sub) in patterns {
+                    if regex::Regex::new(pattern).is_ok_and(|re| re.is_match(key)) {
+                        validate_node(val, sub, root, &sub_path, errors);
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Some(arr), Some(items)) = (instance.as_array(), schema.get("items")) {
+        for (it, val) in arr.iter().enumerate() {
+            validate_node(val, items, root, &format!("{}/{}", path, it), errors);
+        }
+    }
+}
+
+fn has_type(instance: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => instance.is_i64() || instance.is_u64(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        _ => false,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_export_matches_schema() {
+    let (mut app_entries, _) = crate::load_submission(
+        r#"{"scheme": {"element": "Ti", "gs_level": "0", "lasers": "Ti:Sa",
+        "step_level0": "25388.334", "trans_strength0": "1e7", "unit": "cm<sup>-1</sup>"}}"#,
+    )
+    .unwrap();
+    app_entries.submitted_by = "Jane Doe".to_string();
+    app_entries.document.scheme_transitions[0].level_unc = "0.005".to_string();
+    app_entries.document.scheme_transitions[0].strength_input =
+        Some(crate::strength::StrengthInput {
+            quantity: crate::strength::StrengthQuantity::Lifetime,
+            value: 10.0,
+            wavelength: None,
+            j_lower: None,
+            j_upper: None,
+            branching_ratio: Some(0.8),
+        });
+    app_entries.document.saturation_curves.push(
+        crate::SaturationCurve::new_from_parts(
+            "Step 1",
+            "",
+            &crate::SaturationCurveUnit::W,
+            true,
+            "1, 2, 4",
+            "",
+            "3, 4, 4.5",
+            "",
+        )
+        .unwrap(),
+    );
+    app_entries.document.saturation_curves[0]
+        .laser
+        .beam_diameter = Some(2.0);
+    let output: Value =
+        serde_json::from_str(&crate::create_json_output(&app_entries).unwrap()).unwrap();
+    assert!(output["saturation_curves"][0]["derived"]["power_90"].is_number());
+    assert_eq!(validate_submission_json(&output), Vec::new());
+}
+
+#[test]
+fn test_schema_error_paths() {
+    let instance = json!({
+        "format_version": FORMAT_VERSION,
+        "notes": "",
+        "rims_scheme": {"scheme": {
+            "element": "Xx", "lasers": "Ti:Sa", "last_step_to_ip": false, "gs_term": "",
+            "gs_level": "0", "ip_term": "", "unit": "nm", "step_level0": "abc",
+            "step_lowlying0": "yes"
+        }},
+        "references": [{"id": "10.500/abc", "author": "", "year": -1}],
+        "saturation_curves": [],
+    });
+    let errors: Vec<String> = validate_submission_json(&instance)
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert!(errors.contains(&"(root): Required key 'submitted_by' is missing.".to_string()));
+    assert!(errors
+        .iter()
+        .any(|e| e.starts_with("/rims_scheme/scheme/element: ")));
+    assert!(errors.contains(
+        &"/rims_scheme/scheme/step_level0: \"abc\" does not match: A number written as text."
+            .to_string()
+    ));
+    assert!(errors.contains(
+        &"/rims_scheme/scheme/step_lowlying0: Expected boolean, found string.".to_string()
+    ));
+    assert!(errors.iter().any(|e| e.starts_with("/references/0/year: ")));
+    assert_eq!(errors.len(), 5);
+}
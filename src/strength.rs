@@ -0,0 +1,245 @@
+//! Conversion of oscillator strengths and lifetimes to the Einstein A coefficient that is entered
+//! as transition strength of a step.
+//!
+//! The quantity that a transition strength was calculated from is kept with the step and
+//! exported, such that the value can be traced back to the literature.
+
+use std::fmt;
+
+use serde_json::{json, Map, Value};
+use strum_macros::EnumIter;
+
+use crate::diagram::{level_energies, lower_energy};
+use crate::TemplateApp;
+
+/// Factor of `A = A_FACTOR * gf / (g_k * λ²)` with the vacuum wavelength λ in nm.
+const A_FACTOR: f64 = 6.6702e13;
+
+/// Quantity given in the literature.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize, EnumIter,
+)]
+pub enum StrengthQuantity {
+    #[default]
+    Gf,
+    LogGf,
+    Fik,
+    Lifetime,
+}
+
+impl StrengthQuantity {
+    /// Name of the quantity in exported files.
+    pub fn key(&self) -> &'static str {
+        match self {
+            StrengthQuantity::Gf => "gf",
+            StrengthQuantity::LogGf => "log_gf",
+            StrengthQuantity::Fik => "f_ik",
+            StrengthQuantity::Lifetime => "lifetime",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "gf" => Some(StrengthQuantity::Gf),
+            "log_gf" => Some(StrengthQuantity::LogGf),
+            "f_ik" => Some(StrengthQuantity::Fik),
+            "lifetime" => Some(StrengthQuantity::Lifetime),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StrengthQuantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrengthQuantity::Gf => write!(f, "gf"),
+            StrengthQuantity::LogGf => write!(f, "log(gf)"),
+            StrengthQuantity::Fik => write!(f, "f_ik"),
+            StrengthQuantity::Lifetime => write!(f, "Lifetime (ns)"),
+        }
+    }
+}
+
+/// Input that a transition strength was calculated from.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StrengthInput {
+    pub quantity: StrengthQuantity,
+    /// Value of the quantity, lifetimes in ns.
+    pub value: f64,
+    /// Vacuum wavelength of the transition in nm.
+    pub wavelength: Option<f64>,
+    pub j_lower: Option<f64>,
+    pub j_upper: Option<f64>,
+    /// Branching ratio of the upper level into the lower level, only used with lifetimes.
+    pub branching_ratio: Option<f64>,
+}
+
+impl StrengthInput {
+    /// Einstein A coefficient of the transition in s⁻¹.
+    ///
+    /// Oscillator strengths are converted with the wavelength and the statistical weight
+    /// `g = 2J + 1` of the upper (and for f_ik of the lower) level, lifetimes with the branching
+    /// ratio, which is 1 if not given.
+    pub fn einstein_a(&self) -> Result<f64, String> {
+        let gf = match self.quantity {
+            StrengthQuantity::Lifetime => {
+                let branching_ratio = self.branching_ratio.unwrap_or(1.0);
+                if !(branching_ratio > 0.0 && branching_ratio <= 1.0) {
+                    return Err("The branching ratio must be between 0 and 1.".to_string());
+                }
+                if self.value <= 0.0 {
+                    return Err("The lifetime must be positive.".to_string());
+                }
+                return Ok(branching_ratio / (self.value * 1e-9));
+            }
+            StrengthQuantity::Gf => self.value,
+            StrengthQuantity::LogGf => 10f64.powf(self.value),
+            StrengthQuantity::Fik => {
+                let j_lower = self
+                    .j_lower
+                    .ok_or("The J of the lower level is required for f_ik.".to_string())?;
+                (2.0 * j_lower + 1.0) * self.value
+            }
+        };
+        if gf <= 0.0 {
+            return Err(format!("{} must be positive.", self.quantity));
+        }
+        let wavelength = match self.wavelength {
+            Some(wavelength) if wavelength > 0.0 => wavelength,
+            _ => return Err("A positive wavelength is required.".to_string()),
+        };
+        let j_upper = self
+            .j_upper
+            .ok_or("The J of the upper level is required.".to_string())?;
+        Ok(A_FACTOR * gf / ((2.0 * j_upper + 1.0) * wavelength.powi(2)))
+    }
+
+    /// Entry of the exported scheme, optional values are only written if set.
+    pub fn to_json(&self) -> Value {
+        let mut entry = json!({
+            "quantity": self.quantity.key(),
+            "value": self.value,
+        });
+        for (key, value) in [
+            ("wavelength", self.wavelength),
+            ("j_lower", self.j_lower),
+            ("j_upper", self.j_upper),
+            ("branching_ratio", self.branching_ratio),
+        ] {
+            if let Some(value) = value {
+                entry[key] = Value::from(value);
+            }
+        }
+        entry
+    }
+
+    /// Read an entry of a loaded scheme, `None` if it is not valid.
+    pub fn from_json(entry: &Value) -> Option<Self> {
+        let entry: &Map<String, Value> = entry.as_object()?;
+        Some(Self {
+            quantity: StrengthQuantity::from_key(entry.get("quantity")?.as_str()?)?,
+            value: entry.get("value")?.as_f64()?,
+            wavelength: entry.get("wavelength").and_then(|v| v.as_f64()),
+            j_lower: entry.get("j_lower").and_then(|v| v.as_f64()),
+            j_upper: entry.get("j_upper").and_then(|v| v.as_f64()),
+            branching_ratio: entry.get("branching_ratio").and_then(|v| v.as_f64()),
+        })
+    }
+
+    /// Short description for tooltips, e.g., "log(gf) = -0.5".
+    pub fn description(&self) -> String {
+        format!("{} = {}", self.quantity, self.value)
+    }
+}
+
+/// Total angular momentum J from a term symbol, e.g., 2 for "3F2" and 1.5 for "4F3/2" or
+/// "^{4}F_{3/2}". `None` if the term symbol does not end with J.
+pub fn j_from_term(term: &str) -> Option<f64> {
+    let term: String = term.chars().filter(|c| !"${} ".contains(*c)).collect();
+    let term = term.trim_end_matches(')');
+    let j = match term.rsplit_once('_') {
+        Some((_, j)) => j,
+        None => {
+            let start = term
+                .rfind(|c: char| !(c.is_ascii_digit() || c == '/' || c == '.'))
+                .map_or(0, |it| it + 1);
+            &term[start..]
+        }
+    };
+    let j = match j.split_once('/') {
+        Some((num, den)) => num.parse::<f64>().ok()? / den.parse::<f64>().ok()?,
+        None => j.parse::<f64>().ok()?,
+    };
+    match j >= 0.0 && (2.0 * j).fract() == 0.0 {
+        true => Some(j),
+        false => None,
+    }
+}
+
+/// Input for the converter of a step, pre-filled with the vacuum wavelength from the entered
+/// levels and the J values from the term symbols of the step and the level below.
+pub fn step_input(app_entries: &TemplateApp, index: usize) -> StrengthInput {
+    let trans = &app_entries.document.scheme_transitions[index];
+    let upper = level_energies(app_entries, index + 1)
+        .ok()
+        .and_then(|energies| energies[index]);
+    let wavelength = match (upper, lower_energy(app_entries, index)) {
+        (Some(upper), Some(lower)) if upper > lower => Some(1e7 / (upper - lower)),
+        _ => None,
+    };
+    let lower_term = app_entries.document.scheme_transitions[..index]
+        .iter()
+        .rev()
+        .find(|t| !t.level.is_empty())
+        .map_or(&app_entries.document.scheme_gs.term_symbol, |t| {
+            &t.term_symbol
+        });
+
+    StrengthInput {
+        quantity: StrengthQuantity::default(),
+        value: 0.0,
+        wavelength,
+        j_lower: j_from_term(lower_term),
+        j_upper: j_from_term(&trans.term_symbol),
+        branching_ratio: None,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_j_from_term() {
+    assert_eq!(j_from_term("3F2"), Some(2.0));
+    assert_eq!(j_from_term("4F3/2"), Some(1.5));
+    assert_eq!(j_from_term("3G*3"), Some(3.0));
+    assert_eq!(j_from_term("$^{4}F_{3/2}$"), Some(1.5));
+    assert_eq!(j_from_term("5d^{2}6s^{2} (^{3}F_{2})"), Some(2.0));
+    assert_eq!(j_from_term("3F"), None);
+    assert_eq!(j_from_term("3F1/3"), None);
+}
+
+#[test]
+fn test_einstein_a() {
+    // Ti 3F2 - 3G3 at 394.0 nm, log(gf) from the literature
+    let mut input = StrengthInput {
+        quantity: StrengthQuantity::LogGf,
+        value: -0.5,
+        wavelength: Some(394.0),
+        j_lower: Some(2.0),
+        j_upper: Some(3.0),
+        branching_ratio: None,
+    };
+    let a = input.einstein_a().unwrap();
+    assert!((a - A_FACTOR * 10f64.powf(-0.5) / (7.0 * 394.0 * 394.0)).abs() < 1.0);
+
+    // the same transition as f_ik gives the same A
+    input.quantity = StrengthQuantity::Fik;
+    input.value = 10f64.powf(-0.5) / 5.0;
+    assert!((input.einstein_a().unwrap() - a).abs() / a < 1e-12);
+
+    input.quantity = StrengthQuantity::Lifetime;
+    input.value = 10.0;
+    input.branching_ratio = Some(0.5);
+    assert!((input.einstein_a().unwrap() - 5e7).abs() < 1e-3);
+
+    assert_eq!(StrengthInput::from_json(&input.to_json()), Some(input));
+}